@@ -1,4 +1,5 @@
 use super::*;
+use std::cmp;
 
 const EMPTY: [(i8, i8); 8] = [
     (0, 0),
@@ -10,7 +11,7 @@ const EMPTY: [(i8, i8); 8] = [
     (0, 0),
     (0, 0),
 ];
-const ROOK_DIRS: [(i8, i8); 8] = [
+pub(super) const ROOK_DIRS: [(i8, i8); 8] = [
     (-1, 0),
     (1, 0),
     (0, -1),
@@ -20,7 +21,7 @@ const ROOK_DIRS: [(i8, i8); 8] = [
     (0, 0),
     (0, 0),
 ];
-const BISHOP_DIRS: [(i8, i8); 8] = [
+pub(super) const BISHOP_DIRS: [(i8, i8); 8] = [
     (-1, -1),
     (-1, 1),
     (1, -1),
@@ -30,7 +31,7 @@ const BISHOP_DIRS: [(i8, i8); 8] = [
     (0, 0),
     (0, 0),
 ];
-const ROYAL_DIRS: [(i8, i8); 8] = [
+pub(super) const ROYAL_DIRS: [(i8, i8); 8] = [
     (-1, -1),
     (-1, 0),
     (-1, 1),
@@ -40,7 +41,7 @@ const ROYAL_DIRS: [(i8, i8); 8] = [
     (1, -1),
     (0, -1),
 ];
-const KNIGHT_DIRS: [(i8, i8); 8] = [
+pub(super) const KNIGHT_DIRS: [(i8, i8); 8] = [
     (-1, -2),
     (-2, -1),
     (-2, 1),
@@ -72,7 +73,7 @@ pub const MOVE_PROMO: u8 = 0b01000;
 
 // Returns the index and a bool. The returned bool is true iff the computed result
 // is out of bounds.
-fn index_plus_coord(index: i8, coord: (i8, i8)) -> (usize, bool) {
+pub(super) fn index_plus_coord(index: i8, coord: (i8, i8)) -> (usize, bool) {
     let result = index + (coord.0 * 8) + coord.1;
     if result < 0 || result > 63 {
         return (69, true);
@@ -233,7 +234,7 @@ impl ArrayBoard {
         false
     }
 
-    fn is_square_attacked(&self, index: u32, by_white: bool) -> bool {
+    pub fn is_square_attacked(&self, index: u32, by_white: bool) -> bool {
         let forward = if by_white { 1 } else { -1 };
         // println!("checking index: {}", index);
         for diag in [(forward, 1), (forward, -1)] {
@@ -251,63 +252,100 @@ impl ArrayBoard {
         })
     }
 
-    fn legal_castle_moves(&self) -> Vec<BitMove> {
-        let mut moves = Vec::new();
-        for shift in 0..2 {
-            let mask = 1 << (META_CASTLE + shift + if self.white_to_move() { 2 } else { 0 });
-            let castle = self.meta & mask;
-            if castle == 0 {
+    // The squares the piece on `index` attacks, whatever is on them, for the evaluation. Sliders
+    // stop at the first piece in their way.
+    pub fn attacks(&self, index: usize) -> Vec<usize> {
+        let piece = self.get_piece(index);
+        let mut squares = Vec::new();
+        if piece_type(piece) == PieceType::Pawn as u32 {
+            let forward = if is_piece_white(piece) { -1 } else { 1 };
+            for diag in [(forward, -1), (forward, 1)] {
+                let (scan, out_of_bounds) = index_plus_coord(index as i8, diag);
+                if !out_of_bounds {
+                    squares.push(scan);
+                }
+            }
+            return squares;
+        }
+        let Some(&(piece, directions)) = PIECE_DIRS
+            .iter()
+            .find(|(piece_kind, _)| *piece_kind as u32 == piece_type(piece))
+        else {
+            return squares;
+        };
+        let is_multi_step = matches!(
+            piece,
+            PieceType::Bishop | PieceType::Rook | PieceType::Queen
+        );
+        for d in directions {
+            if d == (0, 0) {
                 continue;
             }
-            // Check that all squares between king and rook are empty
-            let is_king_side = shift == 0;
-            let sq_between = match (self.white_to_move(), is_king_side) {
-                (false, false) => [1, 2, 3],
-                (false, true) => [5, 6, 0],
-                (true, false) => [57, 58, 59],
-                (true, true) => [61, 62, 0],
-            };
-            let mut all_empty = true;
-            for square in sq_between {
-                if square == 0 {
-                    continue;
-                }
-                if self.get_piece(square) != 0 {
-                    all_empty = false;
+            let (mut scan, mut out_of_bounds) = index_plus_coord(index as i8, d);
+            while !out_of_bounds {
+                squares.push(scan);
+                if !is_multi_step || self.get_piece(scan) != 0 {
+                    break;
                 }
+                (scan, out_of_bounds) = index_plus_coord(scan as i8, d);
             }
-            if !all_empty {
+        }
+        squares
+    }
+
+    // Generates castles for any back rank setup, so this covers Chess960 as well. Castles are
+    // encoded king-takes-rook.
+    fn legal_castle_moves(&self) -> Vec<BitMove> {
+        let mut moves = Vec::new();
+        let rights = if self.white_to_move() {
+            [CASTLE_WHITE_KING, CASTLE_WHITE_QUEEN]
+        } else {
+            [CASTLE_BLACK_KING, CASTLE_BLACK_QUEEN]
+        };
+        for right in rights {
+            if self.meta & (1 << (META_CASTLE as usize + right)) == 0 {
+                continue;
+            }
+            let king_square = self.find_piece(piece_to_bits(PieceType::King, self.side_to_move()));
+            let rook_square = self.castle_rooks[right] as u32;
+            let back_rank = king_square & ROW_MASK as u32;
+            let is_king_side = rook_square > king_square;
+            let king_dest = back_rank | if is_king_side { 6 } else { 2 };
+            let rook_dest = back_rank | if is_king_side { 5 } else { 3 };
+
+            // Check that every square either piece passes through or lands on is empty, apart from
+            // the castling king and rook themselves.
+            let span = |a: u32, b: u32| cmp::min(a, b)..=cmp::max(a, b);
+            let blocked = span(king_square, king_dest)
+                .chain(span(rook_square, rook_dest))
+                .any(|sq| {
+                    sq != king_square && sq != rook_square && self.get_piece(sq as usize) != 0
+                });
+            if blocked {
                 continue;
             }
 
             // Check that all squares king would have to traverse are safe
-            let transits = match (self.white_to_move(), is_king_side) {
-                (false, false) => [2, 3, 4],
-                (false, true) => [4, 5, 6],
-                (true, false) => [58, 59, 60],
-                (true, true) => [60, 61, 62],
-            };
-            if transits
-                .iter()
-                .any(|&t| self.is_square_attacked(t, !self.white_to_move()))
+            if span(king_square, king_dest)
+                .any(|t| self.is_square_attacked(t, !self.white_to_move()))
             {
                 continue;
             }
-            moves.push(match castle >> META_CASTLE {
-                // e8g8 - black king-side
-                0b0001 => BitMove::create(0o04, 0o06, None, MOVE_CASTLE),
-                // e8c8 - black queen-side
-                0b0010 => BitMove::create(0o04, 0o02, None, MOVE_CASTLE),
-                // e1g1 - white king-side
-                0b0100 => BitMove::create(0o74, 0o76, None, MOVE_CASTLE),
-                // e1c1 - white queen-side
-                0b1000 => BitMove::create(0o74, 0o72, None, MOVE_CASTLE),
-                _ => panic!("Bad castle format {}", castle),
-            });
+            moves.push(BitMove::create(
+                king_square as u8,
+                rook_square as u8,
+                None,
+                MOVE_CASTLE,
+            ));
         }
         moves
     }
 
+    pub(super) fn is_king_attacked(&self, white_king: bool) -> bool {
+        let king = piece_to_bits(PieceType::King, white_king as u8);
+        self.is_square_attacked(self.find_piece(king), !white_king)
+    }
+
     fn is_king_safe_after_move(&self, mv: &BitMove) -> bool {
         let new_board = self.make_move(mv);
         let king = piece_to_bits(PieceType::King, self.side_to_move());
@@ -341,6 +379,12 @@ impl ArrayBoard {
     }
 
     pub fn generate_moves(&self) -> Vec<BitMove> {
+        self.generate_moves_where(|_| true)
+    }
+
+    // The legal moves that `keep` accepts. Moves are filtered before the legality check, which
+    // has to make each of them, so that the ones thrown out cost next to nothing.
+    fn generate_moves_where(&self, keep: impl Fn(&BitMove) -> bool) -> Vec<BitMove> {
         let mut moves: Vec<BitMove> = Vec::new();
         for i in 0..64 {
             let piece = self.get_piece(i);
@@ -350,9 +394,65 @@ impl ArrayBoard {
             moves.append(&mut self.legal_moves_for_piece(piece_type(piece), i as u8));
         }
         moves.append(&mut self.legal_castle_moves());
+        moves.retain(|mv| keep(mv));
         moves = self.filter_king_checks(moves);
         // Reverse sort--higher meta is prioritized.
         moves.sort_unstable_by(|&mv1, &mv2| mv2.meta.cmp(&mv1.meta));
         moves
     }
+
+    // The legal captures and promotions, and the legal moves that are neither, for searches that
+    // try the one kind before generating the other.
+    pub fn generate_noisy_moves(&self) -> Vec<BitMove> {
+        self.generate_moves_where(|mv| mv.meta & (MOVE_CAPTURE | MOVE_PROMO) > 0)
+    }
+
+    pub fn generate_quiet_moves(&self) -> Vec<BitMove> {
+        self.generate_moves_where(|mv| mv.meta & (MOVE_CAPTURE | MOVE_PROMO) == 0)
+    }
+
+    // The legal captures, most valuable victim first and, among those, least valuable attacker
+    // first (MVV-LVA). For the quiescence search.
+    pub fn generate_captures(&self) -> Vec<BitMove> {
+        let mut captures = self.generate_moves_where(|mv| mv.meta & MOVE_CAPTURE > 0);
+        captures.sort_by_key(|mv| {
+            let attacker = piece_type(self.get_piece(mv.source_square as usize));
+            cmp::Reverse(self.captured_piece_type(mv) * 8 - attacker)
+        });
+        captures
+    }
+
+    // The type of the piece `mv` captures, or 0 (PieceType::Empty) if it isn't a capture.
+    pub fn captured_piece_type(&self, mv: &BitMove) -> u32 {
+        if mv.meta & MOVE_CAPTURE == 0 {
+            return PieceType::Empty as u32;
+        }
+        // En passant is the one capture that doesn't land on its victim.
+        match piece_type(self.get_piece(mv.dest_square as usize)) {
+            0 => PieceType::Pawn as u32,
+            victim => victim,
+        }
+    }
+
+    // Whether `mv` is legal here. Cheaper than searching generate_moves, as only the moving
+    // piece's moves are generated.
+    pub fn is_legal_move(&self, mv: &BitMove) -> bool {
+        let piece = self.get_piece(mv.source_square as usize);
+        if piece == 0 || self.is_opponent_piece(piece) {
+            return false;
+        }
+        let mut moves = self.legal_moves_for_piece(piece_type(piece), mv.source_square);
+        if piece_type(piece) == PieceType::King as u32 {
+            moves.append(&mut self.legal_castle_moves());
+        }
+        self.filter_king_checks(moves).contains(mv)
+    }
+
+    // Finds the legal move written as `mv` in long algebraic notation. Going through the legal
+    // moves (rather than BitMove::from_string) recovers the move's flags, such as castling.
+    pub fn find_legal_move(&self, mv: &str) -> Option<BitMove> {
+        self.generate_moves()
+            .into_iter()
+            .find(|legal| legal.to_string() == mv)
+    }
 }
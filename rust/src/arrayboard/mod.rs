@@ -6,6 +6,12 @@ piece type.
 */
 #![allow(dead_code)]
 pub mod generate_moves;
+pub mod san;
+pub mod see;
+pub mod zobrist;
+
+use super::eval::weights;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 // Constants and Enums
 const BOARD_SIZE: u32 = 8;
@@ -27,8 +33,18 @@ const META_CASTLE: u16 = 1;
 const META_CASTLE_MASK: u16 = 0b1111;
 const META_ENPASSANT: u16 = 5;
 const META_ENPASSANT_MASK: u16 = 0b111111;
-const META_KING_CHECK_MASK: u16 = 0b10000000000;
-const META_KING_CHECK: u16 = 10;
+const META_KING_CHECK_MASK: u16 = 0b100000000000;
+const META_KING_CHECK: u16 = 11;
+
+// Castle right bits (shifted by META_CASTLE), also used to index ArrayBoard::castle_rooks.
+const CASTLE_BLACK_KING: usize = 0;
+const CASTLE_BLACK_QUEEN: usize = 1;
+const CASTLE_WHITE_KING: usize = 2;
+const CASTLE_WHITE_QUEEN: usize = 3;
+const STANDARD_CASTLE_ROOKS: [u8; 4] = [0o07, 0o00, 0o77, 0o70];
+
+// UCI_Chess960: castles are written king-takes-rook and FEN castle fields may name rook files.
+pub static CHESS960: AtomicBool = AtomicBool::new(false);
 
 // Fenstrings
 pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
@@ -36,7 +52,7 @@ pub const PERFT2_FEN: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/
 pub const TEST_FEN: &str = "r3k2r/6B1/8/8/8/8/1b4b1/R3K2R b KQk - 0 1";
 pub const TRICKY_FEN: &str = "r3k2r/pPppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
 
-#[derive(FromPrimitive, Copy, Clone)]
+#[derive(FromPrimitive, Copy, Clone, PartialEq)]
 pub enum PieceType {
     Empty = 0,
     Pawn = 1,
@@ -64,13 +80,27 @@ pub struct ArrayBoard {
     // Represents the meta data:
     //   - meta[0] = side to move
     //   - meta[1:4] = castles
-    //   - meta[4:10] = en passant index
-    //   - meta[10] = a king is checked
+    //   - meta[5:10] = en passant index
+    //   - meta[11] = a king is checked
     meta: u16,
+    // Starting square of the rook for each castle right. Always the corners in standard chess, but
+    // anywhere on the back rank in Chess960.
+    castle_rooks: [u8; 4],
+    // Plies since the last capture or pawn move, and the move number, as in FEN.
+    halfmove_clock: u16,
+    fullmove_number: u16,
+    // Material and piece-square table scores by side ([white, black]) and stage ([middlegame,
+    // endgame]), and the game phase, kept up to date as pieces are added and removed so that the
+    // evaluation doesn't have to add up the board.
+    psqt: [[i32; 2]; 2],
+    game_phase: i32,
+    // The Zobrist key of the position, and that of the pawns alone, kept up to date the same way.
+    key: u64,
+    pawn_key: u64,
 }
 
 #[allow(dead_code)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub struct BitMove {
     source_square: u8,
     dest_square: u8,
@@ -115,6 +145,31 @@ fn index_to_algebraic(index: u32) -> String {
     String::from(file) + &rank.to_string()
 }
 
+// Resolves one character of a FEN castle field to its castle right and rook square. Besides the
+// classic KQkq (which, as in X-FEN, mean the outermost rook on that wing), accepts Shredder-FEN
+// file letters so Chess960 positions can be set up unambiguously.
+fn castle_rook_from_fen(board: &[u8; 64], c: char) -> Option<(usize, u8)> {
+    let side = if c.is_ascii_uppercase() { 1 } else { 0 };
+    let back_rank: u8 = if side == 1 { 0o70 } else { 0o00 };
+    let king = piece_to_bits(PieceType::King, side);
+    let rook = piece_to_bits(PieceType::Rook, side);
+    let king_col = (0..8).find(|&col| board[(back_rank + col) as usize] == king)?;
+    let is_rook = |col: &u8| board[(back_rank + col) as usize] == rook;
+    let rook_col = match c.to_ascii_lowercase() {
+        'k' => (king_col + 1..8).rev().find(is_rook)?,
+        'q' => (0..king_col).find(is_rook)?,
+        f @ 'a'..='h' => Some(f as u8 - b'a').filter(is_rook)?,
+        _ => return None,
+    };
+    let right = match (side, rook_col > king_col) {
+        (0, true) => CASTLE_BLACK_KING,
+        (0, false) => CASTLE_BLACK_QUEEN,
+        (_, true) => CASTLE_WHITE_KING,
+        (_, false) => CASTLE_WHITE_QUEEN,
+    };
+    Some((right, back_rank + rook_col))
+}
+
 pub fn is_piece_white(piece: u32) -> bool {
     (PIECE_SIDE_MASK & piece) == 1
 }
@@ -125,45 +180,121 @@ fn piece_to_bits(piece: PieceType, side: u8) -> u8 {
 
 // Struct implementations
 impl ArrayBoard {
-    // Static factory method
+    // Static factory method. Panics if `fen` is malformed; use parse_fen for untrusted input.
     pub fn create_from_fen(fen: &str) -> ArrayBoard {
-        let fen_arr: Vec<&str> = fen.split(' ').collect();
+        match ArrayBoard::parse_fen(fen) {
+            Ok(board) => board,
+            Err(e) => panic!("Bad FEN \"{fen}\": {e}"),
+        }
+    }
+
+    // Parses a FEN string, describing what is wrong with it if it can't be used. The halfmove
+    // clock and fullmove number are optional and default to "0 1".
+    pub fn parse_fen(fen: &str) -> Result<ArrayBoard, String> {
+        let fen_arr: Vec<&str> = fen.split_whitespace().collect();
+        if fen_arr.len() < 4 || fen_arr.len() > 6 {
+            return Err(format!("expected 4 to 6 fields, found {}", fen_arr.len()));
+        }
         let mut board: [u8; 64] = [0; 64];
-        let mut index: usize = 0;
-        for fen_row in fen_arr[0].split('/') {
+        let rows: Vec<&str> = fen_arr[0].split('/').collect();
+        if rows.len() != BOARD_SIZE as usize {
+            return Err(format!("expected 8 ranks, found {}", rows.len()));
+        }
+        for (row, fen_row) in rows.iter().enumerate() {
+            let mut col: usize = 0;
             for c in fen_row.chars() {
-                if c.is_digit(10) {
-                    index += c.to_digit(10).unwrap() as usize;
+                if col >= BOARD_SIZE as usize {
+                    return Err(format!("rank {} has more than 8 squares", 8 - row));
+                }
+                if let Some(empty) = c.to_digit(10) {
+                    col += empty as usize;
                     continue;
                 }
+                let piece = char_to_piece(c);
+                if piece == PieceType::Empty as u32 {
+                    return Err(format!("unknown piece '{c}'"));
+                }
                 let player = if c.is_lowercase() { 0 } else { 1 };
-                board[index] = (player | (char_to_piece(c) << PIECE_TYPE)) as u8;
+                board[row * BOARD_SIZE as usize + col] = (player | (piece << PIECE_TYPE)) as u8;
                 // println!("{:b}", board[index]);
-                index += 1;
+                col += 1;
+            }
+            if col != BOARD_SIZE as usize {
+                return Err(format!("rank {} does not have 8 squares", 8 - row));
+            }
+        }
+        for (side, name) in [(1, "white"), (0, "black")] {
+            let king = piece_to_bits(PieceType::King, side);
+            let kings = board.iter().filter(|&&p| p == king).count();
+            if kings != 1 {
+                return Err(format!("{name} has {kings} kings"));
             }
         }
         // META: Side to play
         let mut meta = 0;
-        if fen_arr[1].starts_with('w') {
-            meta |= 1
+        match fen_arr[1] {
+            "w" => meta |= 1,
+            "b" => (),
+            side => return Err(format!("bad side to move '{side}'")),
         }
         // META: Castles
-        for c in fen_arr[2].chars() {
-            // HACK ALERT: Start one bit over since sideToMove bit is bit 0
-            let ind = match c {
-                'k' => 0b00010,
-                'q' => 0b00100,
-                'K' => 0b01000,
-                'Q' => 0b10000,
-                _ => 0,
-            };
-            meta |= ind;
+        let mut castle_rooks = STANDARD_CASTLE_ROOKS;
+        if fen_arr[2] != "-" {
+            for c in fen_arr[2].chars() {
+                let (right, rook) = castle_rook_from_fen(&board, c)
+                    .ok_or(format!("no rook to castle with for '{c}'"))?;
+                // HACK ALERT: Start one bit over since sideToMove bit is bit 0
+                meta |= 1 << (META_CASTLE as usize + right);
+                castle_rooks[right] = rook;
+            }
         }
         // META: En Passant
-        if !fen_arr[3].eq_ignore_ascii_case("-") {
-            meta |= algebraic_to_index(fen_arr[3]) << 4;
+        if fen_arr[3] != "-" {
+            let ep = fen_arr[3].as_bytes();
+            let ep_rank = if meta & 1 == 1 { b'6' } else { b'3' };
+            if ep.len() != 2 || !(b'a'..=b'h').contains(&ep[0]) || ep[1] != ep_rank {
+                return Err(format!("bad en passant square '{}'", fen_arr[3]));
+            }
+            meta |= algebraic_to_index(fen_arr[3]) << META_ENPASSANT;
+        }
+        let halfmove_clock = match fen_arr.get(4) {
+            Some(clock) => clock
+                .parse::<u16>()
+                .map_err(|_| format!("bad halfmove clock '{clock}'"))?,
+            None => 0,
+        };
+        let fullmove_number = match fen_arr.get(5) {
+            Some(number) => number
+                .parse::<u16>()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or(format!("bad fullmove number '{number}'"))?,
+            None => 1,
+        };
+        let mut new_board = ArrayBoard {
+            board: [0; 64],
+            meta,
+            castle_rooks,
+            halfmove_clock,
+            fullmove_number,
+            psqt: [[0; 2]; 2],
+            game_phase: 0,
+            key: 0,
+            pawn_key: 0,
+        };
+        for (index, &piece) in board.iter().enumerate() {
+            if piece != 0 {
+                new_board.add_piece(index, piece);
+            }
+        }
+        new_board.key ^= new_board.state_key();
+        if new_board.is_king_attacked(!new_board.white_to_move()) {
+            return Err(String::from("the side not to move is in check"));
+        }
+        if new_board.is_king_attacked(new_board.white_to_move()) {
+            new_board.meta |= META_KING_CHECK_MASK;
         }
-        ArrayBoard { board, meta }
+        Ok(new_board)
     }
 
     // Getters ======================================================
@@ -183,6 +314,15 @@ impl ArrayBoard {
         (self.meta & META_SIDE_TO_MOVE_MASK == 0) != (PIECE_SIDE_MASK & piece == 0)
     }
 
+    // Plies since the last capture or pawn move, for the fifty-move rule.
+    pub fn halfmove_clock(&self) -> u16 {
+        self.halfmove_clock
+    }
+
+    pub fn fullmove_number(&self) -> u16 {
+        self.fullmove_number
+    }
+
     pub fn is_king_checked(&self) -> bool {
         self.meta & META_KING_CHECK_MASK > 0
     }
@@ -192,36 +332,47 @@ impl ArrayBoard {
         self.board[index] as u32
     }
 
+    // Material and piece-square table scores, [white, black] x [middlegame, endgame].
+    pub fn psqt(&self) -> [[i64; 2]; 2] {
+        self.psqt.map(|side| side.map(i64::from))
+    }
+
+    pub fn game_phase(&self) -> i64 {
+        self.game_phase as i64
+    }
+
+    // Adds or takes away (`sign` 1 or -1) the scores and keys of `piece` on `index`.
+    fn score_piece(&mut self, index: usize, piece: u8, sign: i32) {
+        let piece_f = piece as usize - 2;
+        let side = !is_piece_white(piece as u32) as usize;
+        let weights = weights();
+        self.psqt[side][0] += sign * weights.mg_table[piece_f][index];
+        self.psqt[side][1] += sign * weights.eg_table[piece_f][index];
+        self.game_phase += sign * weights.phase_table[piece_f];
+        self.key ^= zobrist::piece_key(piece, index);
+        if piece_type(piece as u32) == PieceType::Pawn as u32 {
+            self.pawn_key ^= zobrist::piece_key(piece, index);
+        }
+    }
+
     fn remove_piece(&mut self, index: usize) {
+        let piece = self.board[index];
+        if piece != 0 {
+            self.score_piece(index, piece, -1);
+        }
         self.board[index] = 0;
     }
 
+    // Replaces whatever is on `index`, so captures take the captured piece's scores away.
     fn add_piece(&mut self, index: usize, piece: u8) {
+        self.remove_piece(index);
         self.board[index] = piece;
+        self.score_piece(index, piece, 1);
     }
 
+    // Updates castle rights for a move made by `piece`.
     fn castle_logic(&mut self, bm: &BitMove, piece: u32) {
         if piece_type(piece) == (PieceType::King as u32) {
-            let rook = piece_to_bits(PieceType::Rook, self.side_to_move());
-            match (bm.source_square, bm.dest_square) {
-                (0o04, 0o02) => {
-                    self.remove_piece(0o00);
-                    self.add_piece(0o03, rook);
-                }
-                (0o04, 0o06) => {
-                    self.remove_piece(0o07);
-                    self.add_piece(0o05, rook);
-                }
-                (0o74, 0o72) => {
-                    self.remove_piece(0o70);
-                    self.add_piece(0o73, rook);
-                }
-                (0o74, 0o76) => {
-                    self.remove_piece(0o77);
-                    self.add_piece(0o75, rook);
-                }
-                _ => (),
-            }
             // META_CASTLE = 1;
             if self.white_to_move() {
                 self.meta &= !(0b11000);
@@ -229,28 +380,44 @@ impl ArrayBoard {
                 self.meta &= !(0b00110);
             }
         }
+        // Remove castle possibility when the rook moves or is captured. While a right is held its
+        // rook is still on its starting square, so touching that square means the rook is gone.
+        for right in 0..4 {
+            let rook = self.castle_rooks[right];
+            if bm.source_square == rook || bm.dest_square == rook {
+                self.meta &= !(1 << (META_CASTLE as usize + right));
+            }
+        }
+    }
 
-        // Remove castle possibility when the rook moves
-        if piece_type(piece) == (PieceType::Rook as u32) {
-            // META_CASTLE = 1;
-            self.meta &= match (self.white_to_move(), (bm.source_square & 0b111) == 7) {
-                (false, true) => !(0b00010),
-                (false, false) => !(0b00100),
-                (true, true) => !(0b01000),
-                (true, false) => !(0b10000),
-            };
+    // If `bm` castles, returns the square of the rook it castles with. Castles generated by
+    // generate_moves are flagged and encoded king-takes-rook; a bare two-square king move, as
+    // written in standard chess, castles with the corner rook.
+    fn castling_rook_square(&self, bm: &BitMove, piece: u32) -> Option<u8> {
+        if piece_type(piece) != (PieceType::King as u32) {
+            return None;
         }
-        self.meta &= match (bm.dest_square, self.white_to_move()) {
-            (0o00, true) => !(0b00100),  // white takes black queen's rook
-            (0o07, true) => !(0b00010),  // white takes black king's rook
-            (0o70, false) => !(0b10000), // black takes white queen's rook
-            (0o77, false) => !(0b01000), // black takes white king's rook
-            _ => !(0),
+        if bm.meta & generate_moves::MOVE_CASTLE > 0 {
+            return Some(bm.dest_square);
+        }
+        if (bm.source_square & ROW_MASK) == (bm.dest_square & ROW_MASK)
+            && (bm.source_square & COL_MASK).abs_diff(bm.dest_square & COL_MASK) == 2
+        {
+            let corner = if bm.dest_square > bm.source_square {
+                7
+            } else {
+                0
+            };
+            return Some((bm.source_square & ROW_MASK) | corner);
         }
+        None
     }
 
     pub fn make_move(&self, bit_move: &BitMove) -> ArrayBoard {
         let mut new_board = self.clone();
+        // The castle rights, en passant square and side to move are keyed afresh once they are
+        // updated.
+        new_board.key ^= self.state_key();
 
         let source_piece = self.get_piece(bit_move.source_square as usize);
         let mut end_piece = source_piece as u8;
@@ -260,8 +427,21 @@ impl ArrayBoard {
             panic!("Illegal move: {}", bit_move.to_string());
         }
 
+        let castling_rook = self.castling_rook_square(bit_move, source_piece);
         new_board.castle_logic(&bit_move, source_piece);
 
+        // Castling moves onto the rook, which isn't a capture.
+        let is_capture =
+            castling_rook.is_none() && self.get_piece(bit_move.dest_square as usize) != 0;
+        if is_capture || piece_type(source_piece) == (PieceType::Pawn as u32) {
+            new_board.halfmove_clock = 0;
+        } else {
+            new_board.halfmove_clock += 1;
+        }
+        if !self.white_to_move() {
+            new_board.fullmove_number += 1;
+        }
+
         new_board.meta &= !(META_ENPASSANT_MASK << META_ENPASSANT);
         if piece_type(source_piece) == (PieceType::Pawn as u32) {
             let dest_row = (bit_move.dest_square & ROW_MASK) >> ROW_OFFSET;
@@ -285,16 +465,202 @@ impl ArrayBoard {
                 new_board.meta |= ((ep_row | source_col) << META_ENPASSANT) as u16;
             }
         }
+        new_board.meta &= !META_KING_CHECK_MASK;
         if bit_move.meta & generate_moves::MOVE_CHECK > 0 {
             new_board.meta |= META_KING_CHECK_MASK;
         }
 
         new_board.meta ^= META_SIDE_TO_MOVE_MASK;
         new_board.remove_piece(bit_move.source_square as usize);
-        new_board.add_piece(bit_move.dest_square as usize, end_piece as u8);
+        match castling_rook {
+            // King and rook always land on the same squares as in standard chess: the g and f
+            // files when castling king-side, the c and d files queen-side.
+            Some(rook_square) => {
+                let back_rank = bit_move.source_square & ROW_MASK;
+                let is_king_side = rook_square > bit_move.source_square;
+                let rook = self.get_piece(rook_square as usize) as u8;
+                new_board.remove_piece(rook_square as usize);
+                new_board.add_piece(
+                    (back_rank | if is_king_side { 6 } else { 2 }) as usize,
+                    end_piece,
+                );
+                new_board.add_piece(
+                    (back_rank | if is_king_side { 5 } else { 3 }) as usize,
+                    rook,
+                );
+            }
+            None => new_board.add_piece(bit_move.dest_square as usize, end_piece as u8),
+        }
+        new_board.key ^= new_board.state_key();
+        new_board
+    }
+
+    // Passes the move to the opponent, for null move pruning. Only valid when not in check.
+    pub fn make_null_move(&self) -> ArrayBoard {
+        let mut new_board = *self;
+        new_board.key ^= self.state_key();
+        new_board.meta &= !(META_ENPASSANT_MASK << META_ENPASSANT);
+        new_board.meta &= !META_KING_CHECK_MASK;
+        new_board.meta ^= META_SIDE_TO_MOVE_MASK;
+        new_board.key ^= new_board.state_key();
         new_board
     }
 
+    // How many pieces besides pawns and its king the side to move has. Without any, zugzwang is
+    // common enough that passing can't be assumed to be the worst move, and with just one it is
+    // still far from rare.
+    pub fn non_pawn_piece_count(&self) -> usize {
+        self.board
+            .iter()
+            .filter(|&&piece| {
+                let piece = piece as u32;
+                piece != 0
+                    && !self.is_opponent_piece(piece)
+                    && piece_type(piece) != PieceType::Pawn as u32
+                    && piece_type(piece) != PieceType::King as u32
+            })
+            .count()
+    }
+
+    // Whether neither side has the material left to mate, however badly the other plays: bare
+    // kings, a single minor piece, or only bishops that all move on squares of the same color.
+    pub fn is_insufficient_material(&self) -> bool {
+        let (mut knights, mut bishops) = (0, 0);
+        let mut bishop_colors = [false; 2];
+        for (index, &piece) in self.board.iter().enumerate() {
+            match piece_type(piece as u32) {
+                0 => (),
+                p if p == PieceType::King as u32 => (),
+                p if p == PieceType::Knight as u32 => knights += 1,
+                p if p == PieceType::Bishop as u32 => {
+                    bishops += 1;
+                    let (row, col) = (index as u8 >> ROW_OFFSET, index as u8 & COL_MASK);
+                    bishop_colors[((row + col) % 2) as usize] = true;
+                }
+                _ => return false,
+            }
+        }
+        knights + bishops <= 1 || (knights == 0 && !(bishop_colors[0] && bishop_colors[1]))
+    }
+
+    // FEN AND DISPLAY HELPERS ============================================
+    // Castle rights in FEN notation, e.g. "KQkq", or "-" if neither side may castle. A right
+    // whose rook isn't the outermost one on its wing (only possible in Chess960) is written as the
+    // rook's file instead, Shredder-FEN style.
+    pub fn castling_rights(&self) -> String {
+        let mut rights = String::new();
+        for (right, c) in [
+            (CASTLE_WHITE_KING, 'K'),
+            (CASTLE_WHITE_QUEEN, 'Q'),
+            (CASTLE_BLACK_KING, 'k'),
+            (CASTLE_BLACK_QUEEN, 'q'),
+        ] {
+            if self.meta & (1 << (META_CASTLE as usize + right)) == 0 {
+                continue;
+            }
+            let rook_square = self.castle_rooks[right];
+            match castle_rook_from_fen(&self.board, c) {
+                Some((_, outermost)) if outermost == rook_square => rights.push(c),
+                _ => {
+                    let file = (b'a' + (rook_square & COL_MASK)) as char;
+                    rights.push(if c.is_ascii_uppercase() {
+                        file.to_ascii_uppercase()
+                    } else {
+                        file
+                    });
+                }
+            }
+        }
+        if rights.is_empty() {
+            String::from("-")
+        } else {
+            rights
+        }
+    }
+
+    pub fn enpassant_square(&self) -> Option<String> {
+        match self.get_enpassant() {
+            0 => None,
+            ep => Some(index_to_algebraic(ep as u32)),
+        }
+    }
+
+    pub fn fen(&self) -> String {
+        let mut placement = String::new();
+        for row in 0..BOARD_SIZE as usize {
+            let mut empty = 0;
+            for col in 0..BOARD_SIZE as usize {
+                let piece_bits = self.board[row * BOARD_SIZE as usize + col] as u32;
+                if piece_bits == 0 {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    placement += &empty.to_string();
+                    empty = 0;
+                }
+                let piece = piece_to_char(piece_type(piece_bits), "");
+                if is_piece_white(piece_bits) {
+                    placement += &piece.to_ascii_uppercase();
+                } else {
+                    placement += piece;
+                }
+            }
+            if empty > 0 {
+                placement += &empty.to_string();
+            }
+            if row < 7 {
+                placement += "/";
+            }
+        }
+        format!(
+            "{placement} {} {} {} {} {}",
+            if self.white_to_move() { "w" } else { "b" },
+            self.castling_rights(),
+            self.enpassant_square().unwrap_or(String::from("-")),
+            self.halfmove_clock,
+            self.fullmove_number,
+        )
+    }
+
+    // The same position with the colors swapped: ranks reversed, white's pieces made black's and
+    // black's white's, and the other side to move. For checking that the evaluation is
+    // colorblind.
+    pub fn mirrored(&self) -> ArrayBoard {
+        let swap_case = |field: &str| -> String {
+            field
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_uppercase() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        c.to_ascii_uppercase()
+                    }
+                })
+                .collect()
+        };
+        let fen = self.fen();
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let placement: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+        let enpassant: String = fields[3]
+            .chars()
+            .map(|c| match c {
+                '3' => '6',
+                '6' => '3',
+                c => c,
+            })
+            .collect();
+        ArrayBoard::create_from_fen(&format!(
+            "{} {} {} {} {} {}",
+            placement.join("/"),
+            if self.white_to_move() { "b" } else { "w" },
+            swap_case(fields[2]),
+            enpassant,
+            fields[4],
+            fields[5],
+        ))
+    }
+
     // DEBUGGING AND PRINTING FUNCTIONS ===================================
     pub fn pretty_print(&self, verbose: bool) {
         if verbose {
@@ -352,6 +718,44 @@ impl ArrayBoard {
 }
 
 impl BitMove {
+    // Passing the turn, as the search's null move does: a placeholder in lines of moves, written
+    // "0000" as in UCI.
+    pub const NULL: BitMove = BitMove {
+        source_square: 0,
+        dest_square: 0,
+        promote_to: None,
+        meta: 0,
+    };
+
+    pub fn source_square(&self) -> u8 {
+        self.source_square
+    }
+
+    pub fn dest_square(&self) -> u8 {
+        self.dest_square
+    }
+
+    // Packs the move into 20 bits: source and destination squares, promotion piece type and meta.
+    pub fn encode(&self) -> u32 {
+        let promote_to = self.promote_to.map_or(0, |p| p as u32);
+        (self.source_square as u32)
+            | (self.dest_square as u32) << 6
+            | promote_to << 12
+            | (self.meta as u32) << 15
+    }
+
+    pub fn decode(bits: u32) -> BitMove {
+        BitMove {
+            source_square: (bits & 0o77) as u8,
+            dest_square: (bits >> 6 & 0o77) as u8,
+            promote_to: match bits >> 12 & 0b111 {
+                0 => None,
+                p => num::FromPrimitive::from_u32(p),
+            },
+            meta: (bits >> 15 & 0b11111) as u8,
+        }
+    }
+
     pub fn from_string(mv: &str) -> BitMove {
         let source_square = algebraic_to_index(&mv[..2]) as u8;
         let dest_square = algebraic_to_index(&mv[2..4]) as u8;
@@ -371,8 +775,20 @@ impl BitMove {
     }
 
     pub fn to_string(&self) -> String {
+        if *self == BitMove::NULL {
+            return String::from("0000");
+        }
+        // Castles are stored king-takes-rook, which is also how Chess960 writes them. Standard
+        // chess writes the king's two-square move instead.
+        let dest_square =
+            if self.meta & generate_moves::MOVE_CASTLE > 0 && !CHESS960.load(Ordering::Relaxed) {
+                let is_king_side = self.dest_square > self.source_square;
+                (self.source_square & ROW_MASK) | if is_king_side { 6 } else { 2 }
+            } else {
+                self.dest_square
+            };
         index_to_algebraic(self.source_square as u32)
-            + &index_to_algebraic(self.dest_square as u32)
+            + &index_to_algebraic(dest_square as u32)
             + match self.promote_to {
                 Some(PieceType::Queen) => "q",
                 Some(PieceType::Knight) => "n",
@@ -396,3 +812,82 @@ impl BitMove {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each of these must be rejected with an error, not a panic.
+    fn assert_rejected(fens: &[&str]) {
+        for fen in fens {
+            assert!(ArrayBoard::parse_fen(fen).is_err(), "accepted \"{fen}\"");
+        }
+    }
+
+    #[test]
+    fn parses_well_formed_fens() {
+        for fen in [
+            STARTING_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - - 12 40",
+        ] {
+            assert_eq!(ArrayBoard::parse_fen(fen).unwrap().fen(), fen);
+        }
+        // The clocks may be left out.
+        assert!(ArrayBoard::parse_fen("4k3/8/8/8/8/8/8/4K3 w - -").is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_piece_letters() {
+        assert_rejected(&[
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNX w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/4x3/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/4-3/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        ]);
+    }
+
+    #[test]
+    fn rejects_wrong_rank_counts() {
+        assert_rejected(&[
+            "rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            // Ranks of the wrong length.
+            "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppppppp/7/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnrp/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        ]);
+    }
+
+    #[test]
+    fn rejects_bad_castling_fields() {
+        assert_rejected(&[
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkx - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQk1 - 0 1",
+            // Rights without the rook to castle with.
+            "rnbqkbn1/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "4k3/8/8/8/8/8/8/4K3 w K - 0 1",
+        ]);
+    }
+
+    #[test]
+    fn rejects_bad_en_passant_squares() {
+        assert_rejected(&[
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c9 0 2",
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq i6 0 2",
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c 0 2",
+            // On the wrong rank for the side to move.
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c3 0 2",
+        ]);
+    }
+
+    #[test]
+    fn rejects_non_numeric_clocks() {
+        assert_rejected(&[
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 one",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - -1 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 0",
+        ]);
+    }
+}
@@ -0,0 +1,97 @@
+use super::generate_moves::{MOVE_CAPTURE, MOVE_CASTLE, MOVE_CHECK};
+use super::*;
+
+// Drops the parts of a SAN move that people commonly leave out or write differently, so that
+// "exd8=Q+", "exd8Q" and "exd8q" all compare equal.
+fn normalize_san(san: &str) -> String {
+    let san = san.trim_end_matches(['+', '#', '!', '?']).replace('0', "O");
+    let mut normalized = String::new();
+    for (i, c) in san.chars().enumerate() {
+        match c {
+            'x' | '=' | ':' => (),
+            // A trailing promotion piece may be written in either case.
+            _ if i == san.len() - 1 && i > 0 && c.is_ascii_alphabetic() => {
+                normalized.push(c.to_ascii_uppercase())
+            }
+            _ => normalized.push(c),
+        }
+    }
+    normalized
+}
+
+impl ArrayBoard {
+    // Writes the legal move `mv` in standard algebraic notation, e.g. "Nbd7", "exd6", "O-O",
+    // "e8=Q#".
+    pub fn san(&self, mv: &BitMove) -> String {
+        let piece = self.get_piece(mv.source_square as usize);
+        let mut san = if mv.meta & MOVE_CASTLE > 0 {
+            String::from(if mv.dest_square > mv.source_square {
+                "O-O"
+            } else {
+                "O-O-O"
+            })
+        } else if piece_type(piece) == PieceType::Pawn as u32 {
+            let mut san = String::new();
+            if mv.meta & MOVE_CAPTURE > 0 {
+                san += &index_to_algebraic(mv.source_square as u32)[..1];
+                san.push('x');
+            }
+            san += &index_to_algebraic(mv.dest_square as u32);
+            if let Some(promote_to) = mv.promote_to {
+                san.push('=');
+                san += &piece_to_char(promote_to as u32, "").to_ascii_uppercase();
+            }
+            san
+        } else {
+            let mut san = piece_to_char(piece_type(piece), "").to_ascii_uppercase();
+            // Other pieces of the same kind that could also reach the destination.
+            let rivals: Vec<BitMove> = self
+                .generate_moves()
+                .into_iter()
+                .filter(|other| {
+                    other.dest_square == mv.dest_square
+                        && other.source_square != mv.source_square
+                        && other.meta & MOVE_CASTLE == 0
+                        && self.get_piece(other.source_square as usize) == piece
+                })
+                .collect();
+            let source = index_to_algebraic(mv.source_square as u32);
+            if !rivals.is_empty() {
+                let same_col = |other: &BitMove| {
+                    (other.source_square & COL_MASK) == (mv.source_square & COL_MASK)
+                };
+                let same_row = |other: &BitMove| {
+                    (other.source_square & ROW_MASK) == (mv.source_square & ROW_MASK)
+                };
+                if !rivals.iter().any(same_col) {
+                    san += &source[..1];
+                } else if !rivals.iter().any(same_row) {
+                    san += &source[1..];
+                } else {
+                    san += &source;
+                }
+            }
+            if mv.meta & MOVE_CAPTURE > 0 {
+                san.push('x');
+            }
+            san + &index_to_algebraic(mv.dest_square as u32)
+        };
+        if mv.meta & MOVE_CHECK > 0 {
+            let is_mate = self.make_move(mv).generate_moves().is_empty();
+            san.push(if is_mate { '#' } else { '+' });
+        }
+        san
+    }
+
+    // Finds the legal move written as `san` in standard algebraic notation. Check marks,
+    // annotations, capture marks and the promotion '=' are optional.
+    pub fn find_san_move(&self, san: &str) -> Option<BitMove> {
+        let san = normalize_san(san);
+        if san.is_empty() {
+            return None;
+        }
+        self.generate_moves()
+            .into_iter()
+            .find(|mv| normalize_san(&self.san(mv)) == san)
+    }
+}
@@ -0,0 +1,132 @@
+use super::generate_moves::{
+    index_plus_coord, BISHOP_DIRS, KNIGHT_DIRS, MOVE_CAPTURE, MOVE_CASTLE, ROOK_DIRS, ROYAL_DIRS,
+};
+use super::*;
+use std::cmp;
+
+// Piece values for exchanges, indexed by piece type. The king's is high enough that losing it
+// is never worth it.
+const SEE_VALUES: [i32; 7] = [0, 100, 300, 300, 500, 900, 20000];
+
+// A piece type, the directions it attacks in, and whether it slides along them.
+type Attacker = (PieceType, [(i8, i8); 8], bool);
+
+// Attackers are tried cheapest first; sliders are found through the pieces that already took part
+// in the exchange, as those have been lifted off the board.
+const ATTACKERS: [Attacker; 5] = [
+    (PieceType::Knight, KNIGHT_DIRS, false),
+    (PieceType::Bishop, BISHOP_DIRS, true),
+    (PieceType::Rook, ROOK_DIRS, true),
+    (PieceType::Queen, ROYAL_DIRS, true),
+    (PieceType::King, ROYAL_DIRS, false),
+];
+
+impl ArrayBoard {
+    // The square of the least valuable piece of the given side attacking `index`, if any.
+    fn least_valuable_attacker(&self, index: usize, by_white: bool) -> Option<usize> {
+        let is_attacker = |square: usize, piece: PieceType| {
+            let attacker = self.get_piece(square);
+            piece_type(attacker) == piece as u32 && is_piece_white(attacker) == by_white
+        };
+        let forward = if by_white { 1 } else { -1 };
+        for diag in [(forward, 1), (forward, -1)] {
+            let (scan, out_of_bounds) = index_plus_coord(index as i8, diag);
+            if !out_of_bounds && is_attacker(scan, PieceType::Pawn) {
+                return Some(scan);
+            }
+        }
+        for (piece, directions, is_multi_step) in ATTACKERS {
+            for d in directions {
+                if d == (0, 0) {
+                    continue;
+                }
+                let (mut scan, mut out_of_bounds) = index_plus_coord(index as i8, d);
+                while is_multi_step && !out_of_bounds && self.get_piece(scan) == 0 {
+                    (scan, out_of_bounds) = index_plus_coord(scan as i8, d);
+                }
+                if !out_of_bounds && is_attacker(scan, piece) {
+                    return Some(scan);
+                }
+            }
+        }
+        None
+    }
+
+    // Static exchange evaluation: the material the side to move wins (or, if negative, loses) by
+    // playing `mv`, assuming both sides then keep recapturing on its destination square with
+    // their least valuable piece for as long as that pays.
+    pub fn see(&self, mv: &BitMove) -> i32 {
+        if mv.meta & MOVE_CASTLE > 0 {
+            return 0;
+        }
+        let target = mv.dest_square as usize;
+        let mut board = *self;
+        // gains[i] is what the i-th capture wins, if the exchange stops right after it.
+        let mut gains = vec![SEE_VALUES[self.captured_piece_type(mv) as usize]];
+        let mut on_target = piece_type(self.get_piece(mv.source_square as usize));
+        if let Some(promote_to) = mv.promote_to {
+            gains[0] += SEE_VALUES[promote_to as usize] - SEE_VALUES[PieceType::Pawn as usize];
+            on_target = promote_to as u32;
+        }
+        board.remove_piece(mv.source_square as usize);
+        // En passant takes its pawn off the square beside the source instead, which may open a
+        // line onto the destination.
+        if mv.meta & MOVE_CAPTURE > 0 && self.get_piece(target) == 0 {
+            board.remove_piece((mv.source_square & ROW_MASK | mv.dest_square & COL_MASK) as usize);
+        }
+        let mut by_white = !self.white_to_move();
+        while let Some(square) = board.least_valuable_attacker(target, by_white) {
+            gains.push(SEE_VALUES[on_target as usize] - gains[gains.len() - 1]);
+            on_target = piece_type(board.get_piece(square));
+            board.remove_piece(square);
+            by_white = !by_white;
+        }
+        // Each side may stand pat instead of recapturing, so resolve the exchange from the end.
+        for i in (1..gains.len()).rev() {
+            gains[i - 1] = -cmp::max(-gains[i - 1], gains[i]);
+        }
+        gains[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn see(fen: &str, mv: &str) -> i32 {
+        let board = ArrayBoard::parse_fen(fen).unwrap();
+        board.see(&board.find_legal_move(mv).unwrap())
+    }
+
+    #[test]
+    fn defended_pawn() {
+        // A rook taking a pawn that a pawn defends loses the exchange for a pawn; a pawn taking it
+        // just trades, unless the rook backs it up.
+        assert_eq!(see("4k3/8/3p4/4p3/8/8/8/K3R3 w - - 0 1", "e1e5"), -400);
+        assert_eq!(see("4k3/8/3p4/4p3/3P4/8/8/K7 w - - 0 1", "d4e5"), 0);
+        assert_eq!(see("4k3/8/3p4/4p3/3P4/8/8/K3R3 w - - 0 1", "d4e5"), 100);
+        // Undefended, it is just won.
+        assert_eq!(see("4k3/8/8/4p3/8/8/8/K3R3 w - - 0 1", "e1e5"), 100);
+    }
+
+    #[test]
+    fn rook_batteries() {
+        // The rook behind backs up the one in front through it, so the defending rook can't
+        // recapture...
+        assert_eq!(see("4r1k1/8/8/4p3/8/8/4R3/K3R3 w - - 0 1", "e2e5"), 100);
+        // ...unless it has one behind it too.
+        assert_eq!(see("4r1k1/4r3/8/4p3/8/8/4R3/K3R3 w - - 0 1", "e2e5"), -400);
+        // Alone, the rook in front loses itself for the pawn.
+        assert_eq!(see("4r1k1/8/8/4p3/8/8/4R3/K7 w - - 0 1", "e2e5"), -400);
+    }
+
+    #[test]
+    fn en_passant() {
+        // Taking the d5 pawn en passant opens the d-file for the rook on d1 to recapture.
+        let fen = "4k3/8/8/3pP3/8/8/K7/3r4 w - d6 0 1";
+        assert_eq!(see(fen, "e5d6"), 0);
+        // With the file closed behind, the pawn is just won.
+        let fen = "4k3/8/8/3pP3/8/3P4/K7/3r4 w - d6 0 1";
+        assert_eq!(see(fen, "e5d6"), 100);
+    }
+}
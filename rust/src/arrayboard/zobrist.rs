@@ -0,0 +1,108 @@
+use super::*;
+
+// Zobrist keys, generated at compile time from a fixed seed so that keys are stable between runs
+// and builds.
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    (state, z ^ (z >> 31))
+}
+
+const fn initialize_keys<const N: usize>(seed: u64) -> [u64; N] {
+    let mut keys = [0; N];
+    let mut state = seed;
+    let mut i = 0;
+    while i < N {
+        let (next_state, key) = splitmix64(state);
+        keys[i] = key;
+        state = next_state;
+        i += 1;
+    }
+    keys
+}
+
+// Indexed by [piece bits][square]; the piece bits are at most 0b1101.
+const PIECE_KEYS: [[u64; 64]; 14] = {
+    let flat: [u64; 14 * 64] = initialize_keys(0x5741_4c52_5553);
+    let mut keys = [[0; 64]; 14];
+    let mut i = 0;
+    while i < 14 * 64 {
+        keys[i / 64][i % 64] = flat[i];
+        i += 1;
+    }
+    keys
+};
+// Indexed by the 4 castle bits of the metadata.
+const CASTLE_KEYS: [u64; 16] = initialize_keys(0x4341_5354_4c45);
+// Indexed by the file of the en passant square.
+const ENPASSANT_KEYS: [u64; 8] = initialize_keys(0x454e_5041_5353);
+const WHITE_TO_MOVE_KEY: u64 = splitmix64(0x5749_5445).1;
+
+// The key of `piece` on `sq`, for keys kept up to date piece by piece.
+pub(super) fn piece_key(piece: u8, sq: usize) -> u64 {
+    PIECE_KEYS[piece as usize][sq]
+}
+
+impl ArrayBoard {
+    // The Zobrist key of the position. It is kept up to date as pieces move, by xoring in and out
+    // the keys of the pieces and of the state that moves change.
+    pub fn zobrist_key(&self) -> u64 {
+        self.key
+    }
+
+    // The key of the castle rights, en passant square and side to move.
+    pub(super) fn state_key(&self) -> u64 {
+        let mut key = CASTLE_KEYS[((self.meta >> META_CASTLE) & META_CASTLE_MASK) as usize];
+        let enpassant = self.get_enpassant();
+        if enpassant != 0 {
+            key ^= ENPASSANT_KEYS[(enpassant & COL_MASK) as usize];
+        }
+        if self.white_to_move() {
+            key ^= WHITE_TO_MOVE_KEY;
+        }
+        key
+    }
+
+    // A key of the pawns alone, for tables about the pawn structure, kept up to date as pawns come
+    // and go.
+    pub fn pawn_key(&self) -> u64 {
+        self.pawn_key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Checks the kept key against one computed afresh from the position's FEN, for every position
+    // up to `depth` plies on, and after passing.
+    fn check_keys(board: ArrayBoard, depth: u32) {
+        let fresh = ArrayBoard::parse_fen(&board.fen()).unwrap();
+        assert_eq!(board.zobrist_key(), fresh.zobrist_key(), "{}", board.fen());
+        assert_eq!(board.pawn_key(), fresh.pawn_key(), "{}", board.fen());
+        if depth == 0 {
+            return;
+        }
+        if !board.is_king_checked() {
+            check_keys(board.make_null_move(), 0);
+        }
+        for mv in board.generate_moves() {
+            check_keys(board.make_move(&mv), depth - 1);
+        }
+    }
+
+    #[test]
+    fn kept_keys_match_fresh_ones() {
+        // Castles both ways for both sides, captures, en passant and promotions are all a few plies
+        // away.
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ] {
+            check_keys(ArrayBoard::parse_fen(fen).unwrap(), 3);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use super::arrayboard::{ArrayBoard, STARTING_FEN, TRICKY_FEN};
+use super::engine;
+use super::tt;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+pub const DEFAULT_BENCH_DEPTH: u8 = 4;
+
+// A small spread of openings, middlegames and endgames. Changing this list changes the bench
+// signature, so only append to it deliberately.
+pub const BENCH_FENS: [&str; 8] = [
+    STARTING_FEN,
+    TRICKY_FEN,
+    "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4",
+    "r3r1k1/2p2ppp/p1p1bn2/8/1q2P3/2NPQN2/PPP3PP/R4RK1 b - - 2 15",
+    "rq3rk1/ppp2ppp/1bnpb3/3N2B1/3NP3/7P/PPPQ1PP1/2KR3R w - - 7 14",
+    "8/k7/3p4/p2P1p2/P2P1P2/8/8/K7 w - - 0 1",
+    "6k1/6p1/6Pp/ppp5/3pn2P/1P3K2/1PP2P2/8 b - - 3 54",
+    "8/8/8/8/5kp1/P7/8/1K1N4 w - - 0 80",
+];
+
+// Searches every bench position to `depth` and prints the total node count, which serves as a
+// signature of the search: any functional change to move generation or search changes it.
+pub fn run(depth: u8) {
+    let start = Instant::now();
+    let mut total_nodes = 0;
+    engine::STOP.store(false, Ordering::Relaxed);
+    engine::GAME_HISTORY.lock().unwrap().clear();
+    let limits = engine::SearchLimits {
+        depth,
+        ..Default::default()
+    };
+    for (i, fen) in BENCH_FENS.iter().enumerate() {
+        send!("Position: {}/{} ({fen})", i + 1, BENCH_FENS.len());
+        let board = ArrayBoard::create_from_fen(fen);
+        // Each position starts from an empty table, so the node count doesn't depend on the
+        // table's contents.
+        tt::clear();
+        total_nodes += engine::search(board, &limits, &engine::STOP).nodes;
+    }
+    let tm = start.elapsed().as_millis() as u64;
+    send!();
+    send!("===========================");
+    send!("Total time (ms) : {tm}");
+    send!("Nodes searched  : {total_nodes}");
+    send!(
+        "Nodes/second    : {}",
+        engine::nodes_per_second(total_nodes, tm)
+    );
+}
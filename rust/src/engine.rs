@@ -1,326 +1,1506 @@
-use super::arrayboard::{is_piece_white, ArrayBoard, BitMove};
+use super::arrayboard::generate_moves::{MOVE_CAPTURE, MOVE_CHECK, MOVE_PROMO};
+use super::arrayboard::{ArrayBoard, BitMove};
+use super::eval::{eval, lazy_eval, network_eval};
+use super::kpk;
+use super::nnue;
+use super::params::*;
+use super::timeman;
+use super::trace;
+use super::tt;
+use super::tt::Bound;
+use super::wdl;
 use std::cmp;
-use std::io;
-use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
-
-// PeSTO piece evaluation tables
-#[rustfmt::skip]
-const MG_PAWN_TABLE: [i16;64] = [
-    0,   0,   0,   0,   0,   0,  0,   0,
-    98, 134,  61,  95,  68, 126, 34, -11,
-    -6,   7,  26,  31,  65,  56, 25, -20,
-    -14,  13,   6,  21,  23,  12, 17, -23,
-    -27,  -2,  -5,  12,  17,   6, 10, -25,
-    -26,  -4,  -4, -10,   3,   3, 33, -12,
-    -35,  -1, -20, -23, -15,  24, 38, -22,
-    0,   0,   0,   0,   0,   0,  0,   0,
-];
-
-#[rustfmt::skip]
-const EG_PAWN_TABLE: [i16;64] = [
-      0,   0,   0,   0,   0,   0,   0,   0,
-    178, 173, 158, 134, 147, 132, 165, 187,
-     94, 100,  85,  67,  56,  53,  82,  84,
-     32,  24,  13,   5,  -2,   4,  17,  17,
-     13,   9,  -3,  -7,  -7,  -8,   3,  -1,
-      4,   7,  -6,   1,   0,  -5,  -1,  -8,
-     13,   8,   8,  10,  13,   0,   2,  -7,
-      0,   0,   0,   0,   0,   0,   0,   0,
-];
-
-#[rustfmt::skip]
-const MG_KNIGHT_TABLE: [i16;64] = [
-    0,   0,   0,   0,   0,   0,   0,   0,
-    178, 173, 158, 134, 147, 132, 165, 187,
-    94, 100,  85,  67,  56,  53,  82,  84,
-    32,  24,  13,   5,  -2,   4,  17,  17,
-    13,   9,  -3,  -7,  -7,  -8,   3,  -1,
-    4,   7,  -6,   1,   0,  -5,  -1,  -8,
-    13,   8,   8,  10,  13,   0,   2,  -7,
-    0,   0,   0,   0,   0,   0,   0,   0,
-];
-
-#[rustfmt::skip]
-const EG_KNIGHT_TABLE: [i16;64] = [
-    -58, -38, -13, -28, -31, -27, -63, -99,
-    -25,  -8, -25,  -2,  -9, -25, -24, -52,
-    -24, -20,  10,   9,  -1,  -9, -19, -41,
-    -17,   3,  22,  22,  22,  11,   8, -18,
-    -18,  -6,  16,  25,  16,  17,   4, -18,
-    -23,  -3,  -1,  15,  10,  -3, -20, -22,
-    -42, -20, -10,  -5,  -2, -20, -23, -44,
-    -29, -51, -23, -15, -22, -18, -50, -64,
-];
-
-#[rustfmt::skip]
-const MG_BISHOP_TABLE: [i16;64] = [
-    -29,   4, -82, -37, -25, -42,   7,  -8,
-    -26,  16, -18, -13,  30,  59,  18, -47,
-    -16,  37,  43,  40,  35,  50,  37,  -2,
-     -4,   5,  19,  50,  37,  37,   7,  -2,
-     -6,  13,  13,  26,  34,  12,  10,   4,
-      0,  15,  15,  15,  14,  27,  18,  10,
-      4,  15,  16,   0,   7,  21,  33,   1,
-    -33,  -3, -14, -21, -13, -12, -39, -21,
-];
-
-#[rustfmt::skip]
-const EG_BISHOP_TABLE: [i16;64] = [
-    -14, -21, -11,  -8, -7,  -9, -17, -24,
-     -8,  -4,   7, -12, -3, -13,  -4, -14,
-      2,  -8,   0,  -1, -2,   6,   0,   4,
-     -3,   9,  12,   9, 14,  10,   3,   2,
-     -6,   3,  13,  19,  7,  10,  -3,  -9,
-    -12,  -3,   8,  10, 13,   3,  -7, -15,
-    -14, -18,  -7,  -1,  4,  -9, -15, -27,
-    -23,  -9, -23,  -5, -9, -16,  -5, -17,
-];
-
-#[rustfmt::skip]
-const MG_ROOK_TABLE: [i16;64] = [
-     32,  42,  32,  51, 63,  9,  31,  43,
-     27,  32,  58,  62, 80, 67,  26,  44,
-     -5,  19,  26,  36, 17, 45,  61,  16,
-    -24, -11,   7,  26, 24, 35,  -8, -20,
-    -36, -26, -12,  -1,  9, -7,   6, -23,
-    -45, -25, -16, -17,  3,  0,  -5, -33,
-    -44, -16, -20,  -9, -1, 11,  -6, -71,
-    -19, -13,   1,  17, 16,  7, -37, -26,
-];
-
-#[rustfmt::skip]
-const EG_ROOK_TABLE: [i16;64] = [
-    13, 10, 18, 15, 12,  12,   8,   5,
-    11, 13, 13, 11, -3,   3,   8,   3,
-     7,  7,  7,  5,  4,  -3,  -5,  -3,
-     4,  3, 13,  1,  2,   1,  -1,   2,
-     3,  5,  8,  4, -5,  -6,  -8, -11,
-    -4,  0, -5, -1, -7, -12,  -8, -16,
-    -6, -6,  0,  2, -9,  -9, -11,  -3,
-    -9,  2,  3, -1, -5, -13,   4, -20,
-];
-
-#[rustfmt::skip]
-const MG_QUEEN_TABLE: [i16;64] = [
-    -28,   0,  29,  12,  59,  44,  43,  45,
-    -24, -39,  -5,   1, -16,  57,  28,  54,
-    -13, -17,   7,   8,  29,  56,  47,  57,
-    -27, -27, -16, -16,  -1,  17,  -2,   1,
-     -9, -26,  -9, -10,  -2,  -4,   3,  -3,
-    -14,   2, -11,  -2,  -5,   2,  14,   5,
-    -35,  -8,  11,   2,   8,  15,  -3,   1,
-     -1, -18,  -9,  10, -15, -25, -31, -50,
-];
-
-#[rustfmt::skip]
-const EG_QUEEN_TABLE: [i16;64] = [
-     -9,  22,  22,  27,  27,  19,  10,  20,
-    -17,  20,  32,  41,  58,  25,  30,   0,
-    -20,   6,   9,  49,  47,  35,  19,   9,
-      3,  22,  24,  45,  57,  40,  57,  36,
-    -18,  28,  19,  47,  31,  34,  39,  23,
-    -16, -27,  15,   6,   9,  17,  10,   5,
-    -22, -23, -30, -16, -16, -23, -36, -32,
-    -33, -28, -22, -43,  -5, -32, -20, -41,
-];
-
-#[rustfmt::skip]
-const MG_KING_TABLE: [i16;64] = [
-    -65,  23,  16, -15, -56, -34,   2,  13,
-     29,  -1, -20,  -7,  -8,  -4, -38, -29,
-     -9,  24,   2, -16, -20,   6,  22, -22,
-    -17, -20, -12, -27, -30, -25, -14, -36,
-    -49,  -1, -27, -39, -46, -44, -33, -51,
-    -14, -14, -22, -46, -44, -30, -15, -27,
-      1,   7,  -8, -64, -43, -16,   9,   8,
-    -15,  36,  12, -54,   8, -28,  24,  14,
-];
-
-#[rustfmt::skip]
-const EG_KING_TABLE: [i16;64] = [
-    -74, -35, -18, -18, -11,  15,   4, -17,
-    -12,  17,  14,  17,  17,  38,  23,  11,
-     10,  17,  23,  15,  20,  45,  44,  13,
-     -8,  22,  24,  27,  26,  33,  26,   3,
-    -18,  -4,  21,  24,  27,  23,   9, -11,
-    -19,  -3,  11,  21,  23,  16,   7,  -9,
-    -27, -11,   4,  13,  14,   4,  -5, -17,
-    -53, -34, -21, -11, -28, -14, -24, -43
-];
-
-const MG_PIECE_VALUES: [i16; 6] = [
-    82,   // Pawns
-    337,  // Knights
-    365,  // Bishops
-    477,  // Rooks
-    1025, // Queens
-    0,    // Kings
-];
-const EG_PIECE_VALUES: [i16; 6] = [
-    94,  // Pawns
-    281, // Knights
-    297, // Bishops
-    512, // Rooks
-    936, // Queens
-    0,   // Kings
-];
-
-const MG_PESTO: [[i16; 64]; 6] = [
-    MG_PAWN_TABLE,
-    MG_KNIGHT_TABLE,
-    MG_BISHOP_TABLE,
-    MG_ROOK_TABLE,
-    MG_QUEEN_TABLE,
-    MG_KING_TABLE,
-];
-const EG_PESTO: [[i16; 64]; 6] = [
-    EG_PAWN_TABLE,
-    EG_KNIGHT_TABLE,
-    EG_BISHOP_TABLE,
-    EG_ROOK_TABLE,
-    EG_QUEEN_TABLE,
-    EG_KING_TABLE,
-];
-
-const GAMEPHASE_INCREMENTAL: [i16; 12] = [0, 0, 1, 1, 1, 1, 2, 2, 4, 4, 0, 0];
-const MG_TABLE: [[i16; 64]; 12] = initialize_tables(MG_PIECE_VALUES, MG_PESTO);
-const EG_TABLE: [[i16; 64]; 12] = initialize_tables(EG_PIECE_VALUES, EG_PESTO);
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// The score of mating at the root. Mates further away score one less per ply, so that the search
+// prefers the quickest mate and the slowest way of getting mated.
 const CHECKMATE: i64 = 100000000;
+// Scores at least this far from zero are mates, within MAX_MATE_PLY plies.
+const MAX_MATE_PLY: i64 = 256;
+const MATE_BOUND: i64 = CHECKMATE - MAX_MATE_PLY;
+// No line is searched deeper than this many plies from the root, extensions and quiescence
+// included: a node this deep just returns its evaluation. The per-ply tables are sized for it.
+pub const MAX_PLY: u8 = 128;
+// Extensions made along any one line, at most.
+const MAX_EXTENSIONS: u8 = 16;
 
-pub static MAX_DEPTH: AtomicU8 = AtomicU8::new(6);
+// Iterations searched when no depth is given.
+pub const DEFAULT_DEPTH: u8 = 6;
+// Set by the protocol threads to abort the in-flight search as soon as possible; the stop flag
+// they pass to `search`.
+pub static STOP: AtomicBool = AtomicBool::new(false);
+// Set while the search is pondering (`go ponder`), thinking on the opponent's time about the move
+// it expects them to play. The clock doesn't run until the UCI thread clears it on `ponderhit`,
+// and the result is held back until then, or until the search is stopped.
+pub static PONDERING: AtomicBool = AtomicBool::new(false);
 const DEBUG: bool = true;
+// Toggled by the UCI `debug` command. Enables `info currline` output.
+pub static UCI_DEBUG: AtomicBool = AtomicBool::new(false);
+// Progress is reported at most once per REPORT_INTERVAL_MS, and the clock (for reports and the
+// time limit) is only checked every REPORT_CHECK_NODES nodes.
+const REPORT_INTERVAL_MS: u64 = 1000;
+const REPORT_CHECK_NODES: u64 = 1024;
+// UCI_ShowRefutations: report the line that refutes each root move that fails low.
+pub static SHOW_REFUTATIONS: AtomicBool = AtomicBool::new(false);
+// UCI_AnalyseMode: the GUI is analysing rather than playing a game. Anything that trades accuracy
+// for practical play (contempt, early exits at the root) must check this and stand down.
+pub static ANALYSE_MODE: AtomicBool = AtomicBool::new(false);
+// Contempt, in centipawns: how much worse than even the engine scores a draw for itself, and so
+// how much better for its opponent. Positive values make it avoid draws against weaker opposition.
+pub static CONTEMPT: AtomicI64 = AtomicI64::new(0);
+// UCI_ShowWDL: append win/draw/loss estimates to info lines.
+pub static SHOW_WDL: AtomicBool = AtomicBool::new(false);
+// NormalizeScore: report centipawn scores rescaled so that 100 is an even chance of winning; see
+// wdl::normalize.
+pub static NORMALIZE_SCORE: AtomicBool = AtomicBool::new(false);
+// Set when driven over the xboard protocol: thinking output uses its format instead, and the
+// UCI-only info lines are left out. XBOARD_POST mirrors xboard's post/nopost commands.
+pub static XBOARD_MODE: AtomicBool = AtomicBool::new(false);
+pub static XBOARD_POST: AtomicBool = AtomicBool::new(true);
+// Zobrist keys of the game's positions before the one being searched, oldest first, for
+// repetition detection.
+pub static GAME_HISTORY: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+// Deterministic: searches ignore the clock and start from an empty table, so that a search limited
+// by depth or `go nodes` always plays out the same way, for regression tests and debugging.
+pub static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+// Set in the console play mode, where the search prints no thinking output at all.
+pub static QUIET: AtomicBool = AtomicBool::new(false);
 
-pub const fn initialize_tables(piece_vals: [i16; 6], pesto: [[i16; 64]; 6]) -> [[i16; 64]; 12] {
-    let mut table = [[0; 64]; 12];
-    let mut ptype = 0;
-    while ptype < 6 {
-        let mut sq = 0;
-        while sq < 64 {
-            table[ptype * 2 + 1][sq] = piece_vals[ptype] + pesto[ptype][sq];
-            table[ptype * 2][sq] = piece_vals[ptype] + pesto[ptype][sq ^ 0b111000];
-            sq += 1;
-        }
-        ptype += 1;
-    }
-    table
-}
-
-fn eval(board: ArrayBoard) -> i64 {
-    let mut game_phase = 0;
-    let mut w_mg = 0;
-    let mut b_mg = 0;
-    let mut w_eg = 0;
-    let mut b_eg = 0;
-    for sq in 0..64 {
-        let piece = board.get_piece(sq) as usize;
-        if piece == 0 {
-            continue;
-        }
-        let piece_f = piece - 2;
-        if is_piece_white(piece as u32) {
-            w_mg += MG_TABLE[piece_f][sq] as i64;
-            w_eg += EG_TABLE[piece_f][sq] as i64;
+pub fn nodes_per_second(nodes: u64, millis: u64) -> u64 {
+    nodes * 1000 / cmp::max(millis, 1)
+}
+
+// Converts a score into (win, draw, loss) probabilities in permille, with `material` on the board
+// (see wdl::material). Mates are certain results.
+pub fn score_to_wdl(score: i64, material: i64) -> (u32, u32, u32) {
+    if is_mate_score(score) {
+        return if score > 0 {
+            (1000, 0, 0)
         } else {
-            b_mg += MG_TABLE[piece_f][sq] as i64;
-            b_eg += EG_TABLE[piece_f][sq] as i64;
+            (0, 0, 1000)
+        };
+    }
+    wdl::score_to_wdl(score, material)
+}
+
+// The contempt for a search whose root scores `score` for the engine, with `material` on the board
+// (see wdl::material): the Contempt option, plus a dynamic share that grows with how much better
+// the engine stands, up to DYNAMIC_CONTEMPT, and turns negative when it stands worse. So a draw by
+// repetition is avoided when ahead and taken when behind. The share shrinks as material comes off,
+// when there is less left to play for.
+fn dynamic_contempt(score: i64, material: i64) -> i64 {
+    let (max, half) = (DYNAMIC_CONTEMPT.get(), DYNAMIC_CONTEMPT_HALF.get());
+    let dynamic = max * score / (score.abs() + half) * cmp::min(material, 78) / 78;
+    CONTEMPT.load(Ordering::Relaxed) + dynamic
+}
+
+fn is_mate_score(score: i64) -> bool {
+    score.abs() >= MATE_BOUND
+}
+
+// The number of moves to the mate a mate score announces: positive when the side to move mates,
+// negative when it gets mated.
+fn mate_in_moves(score: i64) -> i64 {
+    let plies = CHECKMATE - score.abs();
+    (plies + 1) / 2 * score.signum()
+}
+
+// Mate scores in the transposition table count from the stored position, as the same position
+// can be reached at different distances from the root.
+fn score_to_tt(score: i64, ply: u8) -> i64 {
+    match score {
+        s if s >= MATE_BOUND => s + ply as i64,
+        s if s <= -MATE_BOUND => s - ply as i64,
+        s => s,
+    }
+}
+
+fn score_from_tt(score: i64, ply: u8) -> i64 {
+    match score {
+        s if s >= MATE_BOUND => s - ply as i64,
+        s if s <= -MATE_BOUND => s + ply as i64,
+        s => s,
+    }
+}
+
+// `bound` says whether `score` is exact or, after an aspiration window failed, only a bound.
+fn print_info(info: &SearchInfo, score: i64, pv: &[BitMove], bound: Bound) {
+    if !DEBUG || QUIET.load(Ordering::Relaxed) {
+        return;
+    }
+    let pv: Vec<String> = pv.iter().map(|mv| mv.to_string()).collect();
+    let pv = pv.join(" ");
+    let (nodes, time) = (info.nodes, info.elapsed_millis());
+    if XBOARD_MODE.load(Ordering::Relaxed) {
+        // "ply score time nodes pv", time in centiseconds. Mates are reported as 100000 + N.
+        let xboard_score = match is_mate_score(score) {
+            true => (100000 + mate_in_moves(score).abs()) * score.signum(),
+            false => score,
+        };
+        // xboard has no way to mark a score as a bound.
+        if XBOARD_POST.load(Ordering::Relaxed) && bound == Bound::Exact {
+            send!(
+                "{} {xboard_score} {} {nodes} {pv}",
+                info.iteration,
+                time / 10
+            );
         }
-        game_phase += GAMEPHASE_INCREMENTAL[piece_f] as i64;
+        return;
     }
-    let (mg_score, eg_score) = if board.white_to_move() {
-        (w_mg - b_mg, w_eg - b_eg)
-    } else {
-        (b_mg - w_mg, b_eg - w_eg)
+    let nps = nodes_per_second(nodes, time);
+    let mut score_str = match is_mate_score(score) {
+        true => format!("mate {}", mate_in_moves(score)),
+        false if NORMALIZE_SCORE.load(Ordering::Relaxed) => {
+            format!("cp {}", wdl::normalize(score, info.root_material))
+        }
+        false => format!("cp {score}"),
     };
-    // Tapered eval: as the game approaches endgame, weigh the end game evaluation more heavily.
-    // Endgame is decided based on number of minor pieces remaining.
-    let mg_phase = cmp::max(game_phase, 24);
-    let eg_phase = 24 - mg_phase;
-    (mg_phase * mg_score + eg_phase * eg_score) / 24
+    if SHOW_WDL.load(Ordering::Relaxed) {
+        let (win, draw, loss) = score_to_wdl(score, info.root_material);
+        score_str += &format!(" wdl {win} {draw} {loss}");
+    }
+    let multipv = match info.multipv {
+        1 => String::new(),
+        _ => format!(" multipv {}", info.pv_index + 1),
+    };
+    match bound {
+        Bound::Exact => (),
+        Bound::Lower => score_str += " lowerbound",
+        Bound::Upper => score_str += " upperbound",
+    }
+    send!(
+        "info depth {} seldepth {}{multipv} score {score_str} nodes {nodes} time {time} \
+         nps {nps} hashfull {} pv {pv}",
+        info.iteration,
+        info.seldepth,
+        tt::hashfull()
+    );
 }
 
-fn print_info(score: i64, mate_in: Option<i8>, nodes: u64, pv: &str) {
-    if !DEBUG {
+// Keeps the GUI up to date during long searches, where new best moves may be far apart: prints
+// the search's progress and the root move being searched, plus (in debug mode) the line currently
+// being searched.
+fn maybe_report_progress(info: &mut SearchInfo) {
+    let time = info.elapsed_millis();
+    if time < info.last_report_ms + REPORT_INTERVAL_MS {
+        return;
+    }
+    info.last_report_ms = time;
+    if XBOARD_MODE.load(Ordering::Relaxed) || QUIET.load(Ordering::Relaxed) {
         return;
     }
-    match mate_in {
-        Some(m) => println!(
-            "info depth {MAX_DEPTH:?} score mate {mi} nodes {nodes} pv {pv}",
-            mi = (m + 1) / 2 * ((score / CHECKMATE) as i8)
-        ),
-        None => println!("info depth {MAX_DEPTH:?} score cp {score} nodes {nodes} pv {pv}"),
+    let nodes = info.nodes;
+    let currmove = match info.line.first() {
+        Some(mv) => format!(" currmove {}", mv.to_string()),
+        None => String::new(),
+    };
+    send!(
+        "info depth {} seldepth {} nodes {nodes} time {time} nps {}{currmove}",
+        info.iteration,
+        info.seldepth,
+        nodes_per_second(nodes, time)
+    );
+    if UCI_DEBUG.load(Ordering::Relaxed) {
+        let moves: Vec<String> = info.line.iter().map(|mv| mv.to_string()).collect();
+        send!("info currline {}", moves.join(" "));
+    }
+}
+
+// What a search may spend on a move.
+#[derive(Default)]
+pub struct SearchLimits {
+    // Iterations to search, at least one.
+    pub depth: u8,
+    // Exact: the search stops on the node that reaches it, and returns the last iteration it
+    // completed.
+    pub nodes: Option<u64>,
+    pub clock: timeman::Clock,
+    // Best lines to report, each with a different first move. 0 is the same as 1.
+    pub multipv: usize,
+    // Root moves to choose from, in UCI notation, as with `go searchmoves`. Empty, or with no
+    // legal moves among them, allows all moves.
+    pub searchmoves: Vec<String>,
+    // Look only for a mate in at most this many moves, as with `go mate`; see search_mate.
+    pub mate: Option<u8>,
+    // As with `go infinite`: the result is held back until the search is stopped, even if it
+    // finishes first.
+    pub infinite: bool,
+}
+
+#[allow(dead_code)]
+pub struct SearchResult {
+    // The principal variation, best move first.
+    pub pv: Vec<BitMove>,
+    pub score: i64,
+    // The last completed iteration, and the deepest ply it reached.
+    pub depth: u8,
+    pub seldepth: u8,
+    pub nodes: u64,
+    // Milliseconds the search took.
+    pub time: u64,
+}
+
+// Searches `board` from the root, stopping early if the protocol thread raises STOP, and returns
+// the best move along with the rest of the result. A search that is stopped before it finds any
+// best move falls back to any legal move, which is still better than forfeiting on time. The move
+// is None only if there are no legal moves.
+pub fn find_best_move(board: ArrayBoard, limits: &SearchLimits) -> (Option<String>, SearchResult) {
+    let result = match limits.mate {
+        Some(moves) => search_mate(board, moves, limits, &STOP),
+        None => search(board, limits, &STOP),
+    };
+    // The GUI expects no bestmove while it still has the engine pondering, or searching without
+    // limit.
+    while (PONDERING.load(Ordering::Relaxed) || limits.infinite) && !STOP.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_millis(1));
+    }
+    let best_move = match result.pv.first() {
+        Some(mv) => Some(mv.to_string()),
+        None => {
+            let moves: Vec<String> = board
+                .generate_moves()
+                .iter()
+                .map(|mv| mv.to_string())
+                .collect();
+            let allowed = moves.iter().find(|mv| limits.searchmoves.contains(mv));
+            allowed.or(moves.first()).cloned()
+        }
+    };
+    (best_move, result)
+}
+
+struct RootMove {
+    mv: BitMove,
+    // From the last search of the move, or -CHECKMATE before the first.
+    score: i64,
+    // The move's line, if it was the best of its MultiPV line last iteration.
+    pv: Vec<BitMove>,
+    // Nodes searched below the move in the iteration in progress.
+    nodes: u64,
+}
+
+// Everything one search keeps across its nodes (and iterations): its progress and limits, and
+// what it has learned about move ordering, indexed by ply where that depends on the node's
+// distance from the root.
+struct SearchInfo<'a> {
+    // Raised (by another thread, or by the search itself when out of time or nodes) to abort.
+    stop: &'a AtomicBool,
+    start: Instant,
+    time: timeman::TimeLimits,
+    // Set until the ponderhit, and until then the time limits (and the node limit) don't apply.
+    // The time limits count from clock_start_ms, the elapsed time at the ponderhit.
+    pondering: bool,
+    clock_start_ms: u64,
+    node_limit: Option<u64>,
+    nodes: u64,
+    // Deepest ply reached by the iteration in progress, including the quiescence search and
+    // extensions; reported as `seldepth`.
+    seldepth: u8,
+    // Depth of the iterative deepening iteration in progress.
+    iteration: u8,
+    // Elapsed time at which the last progress report was printed.
+    last_report_ms: u64,
+    // The root's legal moves, searched in the order of their scores in the last iteration.
+    root_moves: Vec<RootMove>,
+    // Lines to report per iteration, and the line being searched: the root then only searches
+    // the moves from root_moves[pv_index] on.
+    multipv: usize,
+    pv_index: usize,
+    // Triangular PV table: the best line found from the node at each ply, starting with its best
+    // move, which is pasted in front of its child's line whenever the node finds a better move.
+    pv: Vec<Vec<BitMove>>,
+    // The moves leading from the root to the node being searched, and the piece that made each
+    // one along with its destination square.
+    line: Vec<BitMove>,
+    moved: Vec<(usize, usize)>,
+    // The material on the root's board, for the win/draw/loss model.
+    root_material: i64,
+    // How much worse than even a draw is for the engine, in centipawns; see dynamic_contempt.
+    contempt: i64,
+    // The network's accumulators along `line`, when evaluating with a network.
+    accumulators: Option<nnue::Accumulators>,
+    // Zobrist keys of the positions before the node being searched: the game's, then those along
+    // `line`, with NULL_MOVE_KEY for a position that was passed from.
+    keys: Vec<u64>,
+    // Set while searching the reply to a null move, so that it doesn't pass straight back.
+    after_null_move: bool,
+    // Null moves are only tried from this ply on; set while verifying a null move cutoff.
+    null_move_min_ply: u8,
+    // The static evaluation of the node at each ply of `line`, None if it was in check.
+    static_evals: [Option<i64>; MAX_PLY as usize],
+    // Check extensions made along `line`.
+    extensions: u8,
+    // Two quiet moves per ply that recently caused beta cutoffs there, most recent first. A move
+    // that refutes one position often refutes its siblings too.
+    killers: [[Option<BitMove>; 2]; MAX_PLY as usize],
+    // How often each quiet move, by side and from/to square, has caused a beta cutoff, weighted by
+    // the depth searched below it.
+    history: Box<[[[i32; 64]; 64]; 2]>,
+    // The same for captures, by moving piece, destination square and captured piece type.
+    capture_history: Box<[[[i32; 7]; 64]; 14]>,
+    // Quiet move history following an earlier move, which teaches the search good replies to it:
+    // indexed by the earlier move's piece and destination, then by this move's. Shared between
+    // the moves one and two plies back; see continuation_index.
+    continuation_history: Vec<i32>,
+    // How far the static evaluation has been off from what the search found, by side to move and
+    // pawn structure, in 1/CORRECTION_GRAIN centipawns; see corrected_eval.
+    correction_history: Box<[[i32; CORRECTION_SIZE]; 2]>,
+    // Late move reductions, by remaining depth and move number (both capped at 63).
+    reductions: Box<[[u8; 64]; 64]>,
+}
+
+fn continuation_index(earlier: (usize, usize), piece: usize, dest_square: usize) -> usize {
+    ((earlier.0 * 64 + earlier.1) * 14 + piece) * 64 + dest_square
+}
+
+// Stands in for a key in SearchInfo::keys where the line passed.
+const NULL_MOVE_KEY: u64 = 0;
+
+// History scores are halved whenever one would pass this, and at each new iteration, so that they
+// favor recent cutoffs and never overflow.
+const HISTORY_MAX: i32 = 1 << 20;
+
+// Entries in the correction history per side, and the fraction of a centipawn its corrections are
+// kept in. Corrections stay within CORRECTION_MAX centipawns.
+const CORRECTION_SIZE: usize = 16384;
+const CORRECTION_GRAIN: i32 = 256;
+const CORRECTION_MAX: i32 = 300;
+
+impl<'a> SearchInfo<'a> {
+    fn new(limits: &SearchLimits, stop: &'a AtomicBool) -> SearchInfo<'a> {
+        SearchInfo {
+            stop,
+            start: Instant::now(),
+            time: match DETERMINISTIC.load(Ordering::Relaxed) {
+                true => timeman::TimeLimits::default(),
+                false => timeman::allocate(&limits.clock),
+            },
+            pondering: PONDERING.load(Ordering::Relaxed),
+            clock_start_ms: 0,
+            node_limit: limits.nodes,
+            nodes: 0,
+            seldepth: 0,
+            iteration: 0,
+            last_report_ms: 0,
+            root_moves: Vec::new(),
+            multipv: cmp::max(limits.multipv, 1),
+            pv_index: 0,
+            // One past the deepest ply, for its children's empty lines.
+            pv: vec![Vec::new(); MAX_PLY as usize + 1],
+            line: Vec::new(),
+            moved: Vec::new(),
+            root_material: 0,
+            contempt: CONTEMPT.load(Ordering::Relaxed),
+            accumulators: None,
+            keys: GAME_HISTORY.lock().unwrap().clone(),
+            after_null_move: false,
+            null_move_min_ply: 0,
+            static_evals: [None; MAX_PLY as usize],
+            extensions: 0,
+            killers: [[None; 2]; MAX_PLY as usize],
+            history: Box::new([[[0; 64]; 64]; 2]),
+            capture_history: Box::new([[[0; 7]; 64]; 14]),
+            continuation_history: vec![0; 14 * 64 * 14 * 64],
+            correction_history: Box::new([[0; CORRECTION_SIZE]; 2]),
+            reductions: Box::new(late_move_reductions()),
+        }
+    }
+
+    // The score of a draw (by stalemate, repetition, the fifty-move rule or insufficient material)
+    // for the side to move at `ply`. The engine moves at the root and every other ply after it.
+    // Analysis wants the objective score, without contempt.
+    fn draw_score(&self, ply: u8) -> i64 {
+        if ANALYSE_MODE.load(Ordering::Relaxed) {
+            return 0;
+        }
+        if ply.is_multiple_of(2) {
+            -self.contempt
+        } else {
+            self.contempt
+        }
+    }
+
+    fn elapsed_millis(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    // The time the search has used of its limits: since it started, or since the ponderhit.
+    fn clock_millis(&self) -> u64 {
+        self.elapsed_millis() - self.clock_start_ms
+    }
+
+    // Notices a ponderhit: the search goes on where it is, with its time limits counted from now.
+    fn check_ponderhit(&mut self) {
+        if self.pondering && !PONDERING.load(Ordering::Relaxed) {
+            self.pondering = false;
+            self.clock_start_ms = self.elapsed_millis();
+        }
+    }
+
+    // Counts a node at `ply`, stopping the search if that uses up the node limit. Every
+    // REPORT_CHECK_NODES nodes, also stops it if it is out of time, and reports progress.
+    fn visit(&mut self, ply: u8) {
+        self.nodes += 1;
+        self.seldepth = cmp::max(self.seldepth, ply);
+        if !self.pondering && self.node_limit.is_some_and(|limit| self.nodes >= limit) {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+        if self.nodes.is_multiple_of(REPORT_CHECK_NODES) {
+            self.check_ponderhit();
+            if !self.pondering && self.clock_millis() >= self.time.hard {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+            maybe_report_progress(self);
+        }
+    }
+
+    fn reduction(&self, depth: u8, move_number: usize) -> u8 {
+        self.reductions[(depth as usize).min(63)][move_number.min(63)]
+    }
+
+    // Makes `mv`, followed by the line found below it, the best line from `ply`.
+    fn update_pv(&mut self, ply: u8, mv: BitMove) {
+        let (lines, children) = self.pv.split_at_mut(ply as usize + 1);
+        let line = &mut lines[ply as usize];
+        line.clear();
+        line.push(mv);
+        line.extend_from_slice(&children[0]);
+    }
+
+    // Moves on from `board` to `child`, which `mv` leads to.
+    fn push_move(&mut self, board: &ArrayBoard, child: &ArrayBoard, mv: BitMove) {
+        let piece = board.get_piece(mv.source_square() as usize) as usize;
+        self.moved.push((piece, mv.dest_square() as usize));
+        self.line.push(mv);
+        if let Some(accumulators) = &mut self.accumulators {
+            accumulators.push(child);
+        }
+    }
+
+    // Passes from `board` to `child`, for the null move. The piece that moved is none (0), so
+    // that moves after a null move get continuation histories of their own.
+    fn push_null_move(&mut self, child: &ArrayBoard) {
+        self.moved.push((0, 0));
+        self.line.push(BitMove::NULL);
+        if let Some(accumulators) = &mut self.accumulators {
+            accumulators.push(child);
+        }
+    }
+
+    fn pop_move(&mut self) {
+        self.moved.pop();
+        self.line.pop();
+        if let Some(accumulators) = &mut self.accumulators {
+            accumulators.pop();
+        }
+    }
+
+    // The static evaluation of `board`, the position being searched.
+    fn eval(&mut self, board: ArrayBoard) -> i64 {
+        match &mut self.accumulators {
+            Some(accumulators) => network_eval(board, accumulators),
+            None => eval(board),
+        }
+    }
+
+    fn lazy_eval(&mut self, board: ArrayBoard, alpha: i64, beta: i64) -> i64 {
+        match self.accumulators {
+            Some(_) => self.eval(board),
+            None => lazy_eval(board, alpha, beta),
+        }
+    }
+
+    // Whether the position with `key` has been seen before, in the game or along the line. Only
+    // every other position can be the same, with the same side to move, and none from before the
+    // last capture or pawn move, or before a null move: a line that passed is no real game.
+    fn is_repetition(&self, key: u64, halfmove_clock: u16) -> bool {
+        self.keys
+            .iter()
+            .rev()
+            .take_while(|&&k| k != NULL_MOVE_KEY)
+            .take(halfmove_clock as usize)
+            .skip(1)
+            .step_by(2)
+            .any(|&k| k == key)
+    }
+
+    // Indices into continuation_history for `mv` after each of the last two moves.
+    fn continuation_indices(&self, board: &ArrayBoard, mv: &BitMove) -> Vec<usize> {
+        let piece = board.get_piece(mv.source_square() as usize) as usize;
+        let dest_square = mv.dest_square() as usize;
+        self.moved
+            .iter()
+            .rev()
+            .take(2)
+            .map(|&earlier| continuation_index(earlier, piece, dest_square))
+            .collect()
+    }
+
+    fn history_score(&self, board: &ArrayBoard, mv: &BitMove) -> i32 {
+        let side = board.white_to_move() as usize;
+        let continuation: i32 = self
+            .continuation_indices(board, mv)
+            .into_iter()
+            .map(|i| self.continuation_history[i])
+            .sum();
+        self.history[side][mv.source_square() as usize][mv.dest_square() as usize] + continuation
+    }
+
+    fn update_history(&mut self, board: &ArrayBoard, mv: &BitMove, remaining_depth: u8) {
+        let side = board.white_to_move() as usize;
+        let bonus = remaining_depth as i32 * remaining_depth as i32;
+        let entry = &mut self.history[side][mv.source_square() as usize][mv.dest_square() as usize];
+        *entry += bonus;
+        let mut saturated = *entry > HISTORY_MAX;
+        for i in self.continuation_indices(board, mv) {
+            self.continuation_history[i] += bonus;
+            saturated |= self.continuation_history[i] > HISTORY_MAX;
+        }
+        if saturated {
+            self.age_history();
+        }
+    }
+
+    fn capture_history_score(&self, board: &ArrayBoard, mv: &BitMove) -> i32 {
+        let piece = board.get_piece(mv.source_square() as usize) as usize;
+        let captured = board.captured_piece_type(mv) as usize;
+        self.capture_history[piece][mv.dest_square() as usize][captured]
+    }
+
+    fn update_capture_history(&mut self, board: &ArrayBoard, mv: &BitMove, remaining_depth: u8) {
+        let piece = board.get_piece(mv.source_square() as usize) as usize;
+        let captured = board.captured_piece_type(mv) as usize;
+        let entry = &mut self.capture_history[piece][mv.dest_square() as usize][captured];
+        *entry += remaining_depth as i32 * remaining_depth as i32;
+        if *entry > HISTORY_MAX {
+            self.age_history();
+        }
+    }
+
+    fn age_history(&mut self) {
+        for score in self.history.iter_mut().flatten().flatten() {
+            *score /= 2;
+        }
+        for score in self.capture_history.iter_mut().flatten().flatten() {
+            *score /= 2;
+        }
+        for score in self.continuation_history.iter_mut() {
+            *score /= 2;
+        }
+    }
+
+    fn correction_entry(&mut self, board: &ArrayBoard) -> &mut i32 {
+        let side = board.white_to_move() as usize;
+        &mut self.correction_history[side][(board.pawn_key() % CORRECTION_SIZE as u64) as usize]
+    }
+
+    // The static evaluation, corrected by how far off it has been in positions with the same
+    // pawn structure: the evaluation misjudges some structures consistently.
+    fn corrected_eval(&mut self, board: &ArrayBoard, static_eval: i64) -> i64 {
+        static_eval + (*self.correction_entry(board) / CORRECTION_GRAIN) as i64
+    }
+
+    // Moves the correction for `board` towards the difference between what a search of
+    // `depth` plies found and its static evaluation, more so the deeper the search. Only a bound
+    // that lies on the far side of the evaluation says which way it was off.
+    fn update_correction(
+        &mut self,
+        board: &ArrayBoard,
+        depth: u8,
+        static_eval: i64,
+        score: i64,
+        bound: Bound,
+    ) {
+        let informative = match bound {
+            Bound::Exact => true,
+            Bound::Lower => score > static_eval,
+            Bound::Upper => score < static_eval,
+        };
+        if !informative || is_mate_score(score) {
+            return;
+        }
+        let target = (score - static_eval).clamp(-CORRECTION_MAX as i64, CORRECTION_MAX as i64)
+            as i32
+            * CORRECTION_GRAIN;
+        let weight = cmp::min(depth as i32 + 1, 16);
+        let entry = self.correction_entry(board);
+        *entry = (*entry * (CORRECTION_GRAIN - weight) + target * weight) / CORRECTION_GRAIN;
+    }
+
+    fn store_killer(&mut self, ply: u8, mv: BitMove) {
+        let killers = &mut self.killers[ply as usize];
+        if killers[0] != Some(mv) {
+            killers[1] = killers[0];
+            killers[0] = Some(mv);
+        }
+    }
+}
+
+// Reductions grow with the log of both the depth and the move number: the deeper the search and
+// the later the move, the less likely it is to be best.
+fn late_move_reductions() -> [[u8; 64]; 64] {
+    let mut reductions = [[0; 64]; 64];
+    let base = LMR_BASE.get() as f64 / 100.0;
+    let divisor = LMR_DIVISOR.get() as f64 / 100.0;
+    for (depth, row) in reductions.iter_mut().enumerate().skip(1) {
+        for (move_number, r) in row.iter_mut().enumerate().skip(1) {
+            *r = (base + (depth as f64).ln() * (move_number as f64).ln() / divisor) as u8;
+        }
+    }
+    reductions
+}
+
+// Late move pruning applies up to this depth, after the first late_move_count(depth) moves, or
+// half as many when the position isn't improving.
+const LMP_MAX_DEPTH: u8 = 3;
+
+fn late_move_count(depth: u8, improving: bool) -> usize {
+    let count = LMP_BASE.get() as usize + depth as usize * depth as usize;
+    if improving {
+        count
+    } else {
+        count / 2
     }
 }
 
-pub fn search(
+// Reverse futility pruning applies up to this depth, with a margin that grows with the depth and
+// shrinks when the position is improving, as its evaluation is then likely to hold up.
+const REVERSE_FUTILITY_MAX_DEPTH: u8 = 6;
+
+fn reverse_futility_margin(depth: u8, improving: bool) -> i64 {
+    REVERSE_FUTILITY_MARGIN.get() * depth as i64
+        - REVERSE_FUTILITY_IMPROVING.get() * improving as i64
+}
+
+// Null move cutoffs from this depth on are verified; see search_node.
+const NULL_MOVE_VERIFY_DEPTH: u8 = 10;
+
+// Internal iterative reduction applies from this depth on.
+const IIR_MIN_DEPTH: u8 = 4;
+
+// Captures losing more material than see_prune_margin (by static exchange evaluation) are
+// pruned up to this depth.
+const SEE_PRUNE_MAX_DEPTH: u8 = 4;
+
+fn see_prune_margin(depth: u8) -> i32 {
+    -(SEE_PRUNE_MARGIN.get() as i32) * depth as i32
+}
+
+// Razoring applies up to this depth, with a margin that grows with the depth.
+const RAZOR_MAX_DEPTH: u8 = 2;
+
+fn razor_margin(depth: u8) -> i64 {
+    RAZOR_MARGIN.get() * depth as i64
+}
+
+// Futility pruning applies up to this depth, with a margin that grows with the depth, and is wider
+// when the position is improving.
+const FUTILITY_MAX_DEPTH: u8 = 3;
+
+fn futility_margin(depth: u8, improving: bool) -> i64 {
+    FUTILITY_MARGIN.get() * depth as i64 + FUTILITY_IMPROVING.get() * improving as i64
+}
+
+fn is_quiet(mv: &BitMove) -> bool {
+    mv.meta & (MOVE_CAPTURE | MOVE_PROMO) == 0
+}
+
+// Stages of a node's moves, in the order they are tried. Each stage is only generated once the
+// ones before it have run out, so a node that cuts off early never generates the rest.
+#[derive(Copy, Clone, PartialEq)]
+enum Stage {
+    HashMove,
+    // Captures and promotions that don't lose material, by the value of the captured piece and
+    // then by capture history.
+    GoodNoisy,
+    // The ply's killer moves, if they are legal here.
+    Killers,
+    // The other quiet moves, by their history score.
+    Quiets,
+    // The captures that lose material, least losing first.
+    BadNoisy,
+    Done,
+}
+
+// Hands out a node's moves one at a time, generating them in stages. Sorts are stable, so moves
+// that tie keep the generator's order.
+struct MovePicker {
+    stage: Stage,
+    hash_move: Option<BitMove>,
+    killers: [Option<BitMove>; 2],
+    // What is left of the current stage, next move last.
+    moves: Vec<BitMove>,
+    // Put aside by the GoodNoisy stage for the BadNoisy one.
+    bad_noisy: Vec<BitMove>,
+}
+
+impl MovePicker {
+    // `hash_move` must be legal here.
+    fn new(hash_move: Option<BitMove>, killers: [Option<BitMove>; 2]) -> MovePicker {
+        MovePicker {
+            stage: Stage::HashMove,
+            hash_move,
+            killers,
+            moves: hash_move.into_iter().collect(),
+            bad_noisy: Vec::new(),
+        }
+    }
+
+    // Hands out `moves` as they are, for the root, which keeps its own list.
+    fn from_list(moves: &[BitMove]) -> MovePicker {
+        MovePicker {
+            stage: Stage::BadNoisy,
+            hash_move: None,
+            killers: [None; 2],
+            moves: moves.iter().rev().copied().collect(),
+            bad_noisy: Vec::new(),
+        }
+    }
+
+    fn next(&mut self, board: &ArrayBoard, info: &SearchInfo) -> Option<BitMove> {
+        loop {
+            if let Some(mv) = self.moves.pop() {
+                return Some(mv);
+            }
+            let hash_move = self.hash_move;
+            match self.stage {
+                Stage::HashMove => {
+                    let mut noisy: Vec<(BitMove, i32)> = board
+                        .generate_noisy_moves()
+                        .into_iter()
+                        .filter(|&mv| Some(mv) != hash_move)
+                        .map(|mv| (mv, board.see(&mv)))
+                        .collect();
+                    let good = noisy.iter().filter(|(_, see)| *see >= 0);
+                    self.moves = good.map(|&(mv, _)| mv).collect();
+                    self.moves.sort_by_cached_key(|mv| {
+                        let victim = board.captured_piece_type(mv) as i64;
+                        let history = info.capture_history_score(board, mv) as i64;
+                        -(victim * 2 * HISTORY_MAX as i64 + history)
+                    });
+                    noisy.retain(|(_, see)| *see < 0);
+                    noisy.sort_by_key(|&(_, see)| -see);
+                    self.bad_noisy = noisy.into_iter().map(|(mv, _)| mv).collect();
+                    self.stage = Stage::GoodNoisy;
+                }
+                Stage::GoodNoisy => {
+                    self.moves = self
+                        .killers
+                        .into_iter()
+                        .flatten()
+                        .filter(|mv| Some(*mv) != hash_move && is_quiet(mv))
+                        .filter(|mv| board.is_legal_move(mv))
+                        .collect();
+                    self.stage = Stage::Killers;
+                }
+                Stage::Killers => {
+                    // Killers that weren't legal here aren't among these anyway.
+                    let killers = self.killers;
+                    self.moves = board
+                        .generate_quiet_moves()
+                        .into_iter()
+                        .filter(|&mv| Some(mv) != hash_move && !killers.contains(&Some(mv)))
+                        .collect();
+                    self.moves
+                        .sort_by_cached_key(|mv| -info.history_score(board, mv) as i64);
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => {
+                    self.moves = mem::take(&mut self.bad_noisy);
+                    self.stage = Stage::BadNoisy;
+                }
+                Stage::BadNoisy | Stage::Done => {
+                    self.stage = Stage::Done;
+                    return None;
+                }
+            }
+            // Each stage was sorted best first, and is handed out from the back.
+            self.moves.reverse();
+        }
+    }
+}
+
+// Iterative deepening: searches to depth 1, 2, ... up to the limit, reporting each iteration as
+// it completes. When stopped through `stop`, or out of time or nodes, returns the result of the
+// last completed iteration.
+//
+// Each iteration after the first starts from an aspiration window: a narrow window around the
+// previous iteration's score, which is cheap to search as long as the score doesn't move much.
+// If the score falls outside it, the window is widened on that side and the iteration repeated.
+pub fn search(board: ArrayBoard, limits: &SearchLimits, stop: &AtomicBool) -> SearchResult {
+    let (alpha, beta) = (i32::MIN as i64, i32::MAX as i64);
+    if DETERMINISTIC.load(Ordering::Relaxed) {
+        tt::clear();
+    }
+    tt::new_search();
+    if trace::enabled() {
+        trace::search_started(&board.fen());
+    }
+    let mut best: (Vec<BitMove>, i64) = (Vec::new(), 0);
+    let (mut depth, mut seldepth) = (0, 0);
+    let mut info = SearchInfo::new(limits, stop);
+    info.root_material = wdl::material(&board);
+    info.accumulators = nnue::Accumulators::new(&board);
+    // Until an iteration has scored the root, by its static evaluation.
+    let root_eval = info.eval(board);
+    info.contempt = dynamic_contempt(root_eval, info.root_material);
+    // The first iteration tries the root moves in the usual order, led by the hash move that an
+    // earlier search of the position may have left.
+    let hash_move = tt::probe(board.zobrist_key())
+        .and_then(|entry| entry.best_move)
+        .filter(|mv| board.is_legal_move(mv));
+    let mut picker = MovePicker::new(hash_move, [None; 2]);
+    let mut root_moves = Vec::new();
+    while let Some(mv) = picker.next(&board, &info) {
+        root_moves.push(mv);
+    }
+    if root_moves
+        .iter()
+        .any(|mv| limits.searchmoves.contains(&mv.to_string()))
+    {
+        root_moves.retain(|mv| limits.searchmoves.contains(&mv.to_string()));
+    }
+    info.root_moves = root_moves
+        .into_iter()
+        .map(|mv| RootMove {
+            mv,
+            score: -CHECKMATE,
+            pv: Vec::new(),
+            nodes: 0,
+        })
+        .collect();
+    // Percent of the soft limit to use, and what goes into it; see timeman::soft_scale.
+    let mut soft_scale = 100;
+    let mut best_move_changes = 0;
+    for iteration in 1..=limits.depth.clamp(1, MAX_PLY - 1) {
+        // The last iteration took long enough that the next one, longer still, wouldn't finish.
+        info.check_ponderhit();
+        if iteration > 1
+            && !info.pondering
+            && info.clock_millis() >= info.time.scaled_soft(soft_scale)
+        {
+            break;
+        }
+        // With a single legal move, there's nothing to think about.
+        if iteration > 1 && info.time.flexible && info.root_moves.len() == 1 {
+            break;
+        }
+        let previous_best_move = best.0.first().copied();
+        let iteration_start_nodes = info.nodes;
+        for root in info.root_moves.iter_mut() {
+            root.nodes = 0;
+        }
+        info.iteration = iteration;
+        info.seldepth = 0;
+        info.age_history();
+        if trace::enabled() {
+            trace::iteration_started(iteration);
+        }
+        // MultiPV: each line after the first is the best line among the root moves that the
+        // lines before it didn't take, so the root is searched again with those left out.
+        for pv_index in 0..cmp::max(cmp::min(info.multipv, info.root_moves.len()), 1) {
+            info.pv_index = pv_index;
+            // The move in this place last iteration, whose line is shown if no move makes it.
+            let (previous_score, previous_pv) = match info.root_moves.get(pv_index) {
+                Some(root) => (root.score, root.pv.clone()),
+                None => (0, Vec::new()),
+            };
+            let mut delta = ASPIRATION_WINDOW.get();
+            let (mut window_alpha, mut window_beta) =
+                if iteration > 1 && !is_mate_score(previous_score) {
+                    (
+                        cmp::max(alpha, previous_score - delta),
+                        cmp::min(beta, previous_score + delta),
+                    )
+                } else {
+                    (alpha, beta)
+                };
+            loop {
+                let score = search_node(board, window_alpha, window_beta, 0, iteration, &mut info);
+                let pv = info.pv[0].clone();
+                info.root_moves[pv_index..].sort_by_key(|root| -root.score);
+                if info.stopped() {
+                    // An unfinished iteration is only better than nothing.
+                    if pv_index == 0 && best.0.is_empty() {
+                        best = (pv, score);
+                    }
+                    break;
+                }
+                delta *= 2;
+                if score <= window_alpha && window_alpha > alpha {
+                    print_info(&info, score, &previous_pv, Bound::Upper);
+                    window_alpha = cmp::max(alpha, score - delta);
+                } else if score >= window_beta && window_beta < beta {
+                    print_info(&info, score, &pv, Bound::Lower);
+                    window_beta = cmp::min(beta, score + delta);
+                } else {
+                    print_info(&info, score, &pv, Bound::Exact);
+                    if let Some(root) = info.root_moves.get_mut(pv_index) {
+                        root.pv = pv.clone();
+                    }
+                    if pv_index == 0 {
+                        best = (pv, score);
+                        (depth, seldepth) = (iteration, info.seldepth);
+                    }
+                    break;
+                }
+            }
+            if info.stopped() {
+                break;
+            }
+        }
+        if info.stopped() {
+            break;
+        }
+        let best_move_changed = iteration > 1 && best.0.first().copied() != previous_best_move;
+        best_move_changes = best_move_changes / 2 + 100 * best_move_changed as u64;
+        let iteration_nodes = cmp::max(info.nodes - iteration_start_nodes, 1);
+        let best_move_share = match best.0.first() {
+            Some(&mv) => {
+                let root = info.root_moves.iter().find(|root| root.mv == mv);
+                root.map_or(0, |root| root.nodes * 1000 / iteration_nodes)
+            }
+            None => 0,
+        };
+        soft_scale = timeman::soft_scale(best_move_share, best_move_changes);
+        info.contempt = dynamic_contempt(best.1, info.root_material);
+    }
+    trace::flush();
+    let (pv, score) = best;
+    SearchResult {
+        pv,
+        score,
+        depth,
+        seldepth,
+        nodes: info.nodes,
+        time: info.elapsed_millis(),
+    }
+}
+
+// Mate search, for `go mate`: looks for the shortest forced mate in at most `moves` moves,
+// searching for a mate in one, then two, and so on, and returns as soon as one is proven. The
+// evaluation plays no part: a line either mates or scores 0. Only the depth limit and the bounds
+// on how soon a mate can come prune the tree. The pv is empty if there is no such mate.
+pub fn search_mate(
+    board: ArrayBoard,
+    moves: u8,
+    limits: &SearchLimits,
+    stop: &AtomicBool,
+) -> SearchResult {
+    let mut info = SearchInfo::new(limits, stop);
+    let mut result = (Vec::new(), 0, 0);
+    for n in 1..=cmp::min(moves, MAX_PLY / 2) {
+        // The mating side makes the last move.
+        let depth = 2 * n - 1;
+        info.iteration = depth;
+        let score = mate_node(board, 0, CHECKMATE, 0, depth, &mut info);
+        if info.stopped() {
+            break;
+        }
+        if score > 0 {
+            print_info(&info, score, &info.pv[0], Bound::Exact);
+            result = (info.pv[0].clone(), score, depth);
+            break;
+        }
+    }
+    let (pv, score, depth) = result;
+    SearchResult {
+        pv,
+        score,
+        depth,
+        seldepth: info.seldepth,
+        nodes: info.nodes,
+        time: info.elapsed_millis(),
+    }
+}
+
+fn mate_node(
+    board: ArrayBoard,
+    mut alpha: i64,
+    mut beta: i64,
+    ply: u8,
+    depth: u8,
+    info: &mut SearchInfo,
+) -> i64 {
+    info.pv[ply as usize].clear();
+    if info.stopped() || ply >= MAX_PLY {
+        return 0;
+    }
+    info.visit(ply);
+    let mut moves = board.generate_moves();
+    if moves.is_empty() {
+        return if board.is_king_checked() {
+            -CHECKMATE + ply as i64
+        } else {
+            0
+        };
+    }
+    if depth == 0 {
+        return 0;
+    }
+    alpha = cmp::max(alpha, -CHECKMATE + ply as i64);
+    beta = cmp::min(beta, CHECKMATE - ply as i64 - 1);
+    if alpha >= beta {
+        return alpha;
+    }
+    // Checks first, as they are the most forcing; the last move of the mate must be one.
+    moves.sort_by_key(|mv| mv.meta & MOVE_CHECK == 0);
+    for mv in moves {
+        if depth == 1 && mv.meta & MOVE_CHECK == 0 {
+            break;
+        }
+        let score = -mate_node(
+            board.make_move(&mv),
+            -beta,
+            -alpha,
+            ply + 1,
+            depth - 1,
+            info,
+        );
+        if info.stopped() {
+            break;
+        }
+        if score >= beta {
+            info.update_pv(ply, mv);
+            return beta;
+        }
+        if score > alpha {
+            alpha = score;
+            info.update_pv(ply, mv);
+        }
+    }
+    alpha
+}
+
+// Quiescence search plies, counted from the horizon, that also try quiet moves that give check.
+const QUIESCE_CHECK_PLIES: u8 = 1;
+
+// Quiescence search, run at the horizon: keeps playing out captures until the position is quiet,
+// so that the evaluation isn't taken in the middle of an exchange. The side to move may instead
+// "stand pat" on the static evaluation, as it needn't capture. Its first QUIESCE_CHECK_PLIES plies
+// (`qply` counts them) try checks as well, so that a mate just past the horizon isn't missed, and
+// a side in check may neither stand pat nor ignore the check: it tries all its moves.
+fn quiesce(
     board: ArrayBoard,
     mut alpha: i64,
     beta: i64,
+    ply: u8,
+    qply: u8,
+    info: &mut SearchInfo,
+) -> i64 {
+    if info.stopped() {
+        return 0;
+    }
+    if ply >= MAX_PLY {
+        return info.eval(board);
+    }
+    info.visit(ply);
+    let in_check = board.is_king_checked();
+    let moves = if in_check {
+        let evasions = board.generate_moves();
+        if evasions.is_empty() {
+            return -CHECKMATE + ply as i64;
+        }
+        evasions
+    } else {
+        let stand_pat = info.lazy_eval(board, alpha, beta);
+        if stand_pat >= beta {
+            return beta;
+        }
+        alpha = cmp::max(alpha, stand_pat);
+        let mut moves = board.generate_captures();
+        if qply < QUIESCE_CHECK_PLIES {
+            let quiet_checks = board.generate_quiet_moves().into_iter();
+            moves.extend(quiet_checks.filter(|mv| mv.meta & MOVE_CHECK > 0));
+        }
+        moves
+    };
+    for mv in moves {
+        // Captures that lose material can't do better than standing pat.
+        if !in_check && mv.meta & MOVE_CAPTURE > 0 && board.see(&mv) < 0 {
+            continue;
+        }
+        let child = board.make_move(&mv);
+        info.push_move(&board, &child, mv);
+        let score = -quiesce(child, -beta, -alpha, ply + 1, qply + 1, info);
+        info.pop_move();
+        if info.stopped() {
+            break;
+        }
+        if score >= beta {
+            return beta;
+        }
+        alpha = cmp::max(alpha, score);
+    }
+    alpha
+}
+
+// `ply` is the distance of `board` from the root, and `depth` the number of plies left to search
+// below it before the quiescence search takes over.
+fn search_node(
+    board: ArrayBoard,
+    alpha: i64,
+    beta: i64,
+    ply: u8,
     depth: u8,
-) -> (String, i64, Option<i8>, u64) {
-    if depth == MAX_DEPTH.load(Ordering::Relaxed) {
-        return ("".to_string(), eval(board), None, /* nodes */ 1);
+    info: &mut SearchInfo,
+) -> i64 {
+    let score = search_node_untraced(board, alpha, beta, ply, depth, info);
+    if trace::enabled() {
+        trace::node(&info.line, depth, alpha, beta, score);
+    }
+    score
+}
+
+fn search_node_untraced(
+    board: ArrayBoard,
+    mut alpha: i64,
+    mut beta: i64,
+    ply: u8,
+    mut depth: u8,
+    info: &mut SearchInfo,
+) -> i64 {
+    info.pv[ply as usize].clear();
+    if info.stopped() {
+        return 0;
+    }
+    if ply >= MAX_PLY {
+        return info.eval(board);
+    }
+    info.visit(ply);
+    let key = board.zobrist_key();
+    // A repeated position is scored as a draw: if neither side deviates it will repeat again, and
+    // whoever is worse off can make sure it does.
+    if ply > 0 && info.is_repetition(key, board.halfmove_clock()) {
+        return info.draw_score(ply);
+    }
+    // Neither side can win a dead position, whatever the evaluation says about the material.
+    if ply > 0 && board.is_insufficient_material() {
+        return info.draw_score(ply);
+    }
+    // Nor a king and pawn against king that the bitbase says is drawn.
+    if ply > 0 && kpk::probe(&board) == Some(false) {
+        return info.draw_score(ply);
+    }
+    // Fifty-move rule: a hundred plies without a capture or pawn move is a draw, unless the last
+    // one mated. Wins that take longer than that aren't wins.
+    if ply > 0
+        && board.halfmove_clock() >= 100
+        && !(board.is_king_checked() && board.generate_moves().is_empty())
+    {
+        return info.draw_score(ply);
+    }
+    if depth == 0 {
+        return quiesce(board, alpha, beta, ply, 0, info);
+    }
+    // Mate distance pruning: no line from here can mate faster than mating right now, or get mated
+    // slower than getting mated right now, so a window outside those bounds can't be met.
+    if ply > 0 {
+        alpha = cmp::max(alpha, -CHECKMATE + ply as i64);
+        beta = cmp::min(beta, CHECKMATE - ply as i64 - 1);
+        if alpha >= beta {
+            return alpha;
+        }
+    }
+    let entry = tt::probe(key);
+    // The root always searches, so that it has a move to play.
+    if ply > 0
+        && let Some(entry) = entry.filter(|entry| entry.depth >= depth)
+    {
+        info.pv[ply as usize].extend(entry.best_move);
+        let score = score_from_tt(entry.score, ply);
+        match entry.bound {
+            Bound::Exact => return score,
+            Bound::Lower if score >= beta => return beta,
+            Bound::Upper if score <= alpha => return alpha,
+            _ => (),
+        }
+    }
+    // Null move pruning: if the position is so good that even passing fails high, a real move
+    // would too, so a search reduced by R plies is enough to prove the cutoff. In check passing
+    // is illegal, and with only pawns left zugzwang makes passing the best move too often.
+    let after_null_move = mem::take(&mut info.after_null_move);
+    // Evaluated once per position: a position already in the table has its evaluation there.
+    // The table keeps the uncorrected evaluation, as the corrections keep changing.
+    let raw_eval = match entry {
+        Some(entry) => entry.static_eval,
+        None => info.eval(board),
+    };
+    let static_eval = info.corrected_eval(&board, raw_eval);
+    let in_check = board.is_king_checked();
+    // Improving: the static evaluation is better than at the side's previous turn, two plies up
+    // (or four, if that was in check). A side whose position is improving is pruned less
+    // eagerly, one whose position is getting worse more. In check there's no evaluation to go by.
+    info.static_evals[ply as usize] = if in_check { None } else { Some(static_eval) };
+    let earlier_eval = match ply {
+        0 | 1 => None,
+        2 | 3 => info.static_evals[ply as usize - 2],
+        _ => info.static_evals[ply as usize - 2].or(info.static_evals[ply as usize - 4]),
+    };
+    let improving = !in_check && earlier_eval.is_none_or(|earlier| static_eval > earlier);
+    // Reverse futility pruning: close to the horizon, a position whose static evaluation beats
+    // beta by more than the opponent could plausibly win back is taken to fail high.
+    if ply > 0
+        && depth <= REVERSE_FUTILITY_MAX_DEPTH
+        && !is_mate_score(beta)
+        && !in_check
+        && static_eval - reverse_futility_margin(depth, improving) >= beta
+    {
+        return beta;
+    }
+    // Razoring: the opposite case, a position so far below alpha that only winning material could
+    // save it. The quiescence search, which tries just that, decides whether it fails low.
+    if ply > 0
+        && depth <= RAZOR_MAX_DEPTH
+        && !is_mate_score(alpha)
+        && !in_check
+        && static_eval + razor_margin(depth) <= alpha
+        && quiesce(board, alpha, alpha + 1, ply, 0, info) <= alpha
+        && !info.stopped()
+    {
+        return alpha;
     }
-    let moves = board.generate_moves();
-    if moves.len() == 0 {
-        if board.is_king_checked() {
-            return ("".to_string(), -CHECKMATE, Some(1), 1);
+    let non_pawn_pieces = board.non_pawn_piece_count();
+    if ply > 0
+        && !after_null_move
+        && ply >= info.null_move_min_ply
+        && depth >= 3
+        && beta < MATE_BOUND
+        && !in_check
+        && non_pawn_pieces > 0
+        && static_eval >= beta
+    {
+        let reduction = match depth > 6 {
+            true => NULL_MOVE_DEEP_REDUCTION.get(),
+            false => NULL_MOVE_REDUCTION.get(),
+        } as u8;
+        info.after_null_move = true;
+        let reduced_depth = (depth - 1).saturating_sub(reduction);
+        let child = board.make_null_move();
+        info.push_null_move(&child);
+        info.keys.push(NULL_MOVE_KEY);
+        let score = search_node(child, -beta, -beta + 1, ply + 1, reduced_depth, info);
+        info.keys.pop();
+        info.pop_move();
+        info.after_null_move = false;
+        // A mate found after passing doesn't prove anything about the real moves.
+        if -score >= beta && !is_mate_score(score) && !info.stopped() {
+            // Verification: deep cutoffs, and those with a single piece left where zugzwang is
+            // a real risk, are only trusted once a search of the real moves to the reduced depth
+            // fails high too. It may not pass in turn for most of its depth, or it could just
+            // repeat the same mistake.
+            if depth < NULL_MOVE_VERIFY_DEPTH && non_pawn_pieces > 1 {
+                return beta;
+            }
+            let min_ply = info.null_move_min_ply;
+            info.null_move_min_ply = ply + 3 * reduced_depth / 4 + 1;
+            let verified = search_node(board, beta - 1, beta, ply, reduced_depth, info);
+            info.null_move_min_ply = min_ply;
+            if verified >= beta && !info.stopped() {
+                return beta;
+            }
         }
-        return ("".to_string(), 0, None, 1);
     }
+    // The hash move is searched before the other moves are even generated, as it often cuts off
+    // on its own. The table can hold moves from other positions, so it needs checking first. The
+    // root has its own move list instead.
+    let hash_move = entry
+        .and_then(|entry| entry.best_move)
+        .filter(|mv| ply > 0 && board.is_legal_move(mv));
+    // Internal iterative reduction: a PV node without a hash move will be poorly ordered, and the
+    // search of it is likely to be wasted. Searching it one ply shallower costs less and leaves a
+    // hash move behind for when the next iteration comes back to it.
+    if ply > 0 && hash_move.is_none() && depth >= IIR_MIN_DEPTH && beta - alpha > 1 {
+        depth -= 1;
+    }
+    let mut picker = if ply == 0 {
+        let root_moves: Vec<BitMove> = info.root_moves[info.pv_index..]
+            .iter()
+            .map(|root| root.mv)
+            .collect();
+        MovePicker::from_list(&root_moves)
+    } else {
+        MovePicker::new(hash_move, info.killers[ply as usize])
+    };
 
-    let mut nodes = 0;
-    let mut best_mate_in: Option<i8> = None;
-    let mut best_pv: String = String::from("");
+    let original_alpha = alpha;
+    // The root's later MultiPV lines leave moves out, so what they find isn't the position's
+    // result and stays out of the table.
+    let storable = ply > 0 || info.pv_index == 0;
+    let mut best_move: Option<BitMove> = None;
 
-    for (i, mv) in moves.into_iter().enumerate() {
-        if depth == 0 {
-            println!("info currmove {} currmovenumber {i}", mv.to_string());
+    let mut i = 0;
+    while let Some(mv) = picker.next(&board, info) {
+        i += 1;
+        // Late move pruning: close to the horizon, once enough moves have been tried, the quiet
+        // moves left at the back of the ordering are skipped altogether.
+        if ply > 0
+            && depth <= LMP_MAX_DEPTH
+            && i > late_move_count(depth, improving)
+            && is_quiet(&mv)
+            && !in_check
+            && mv.meta & MOVE_CHECK == 0
+        {
+            continue;
+        }
+        // Captures that lose a lot of material are unlikely to be worth it close to the horizon.
+        if ply > 0
+            && i > 1
+            && depth <= SEE_PRUNE_MAX_DEPTH
+            && mv.meta & MOVE_CAPTURE > 0
+            && !in_check
+            && mv.meta & MOVE_CHECK == 0
+            && board.see(&mv) < see_prune_margin(depth)
+        {
+            continue;
+        }
+        // Futility pruning: close to the horizon, a quiet move can't gain much, so when the
+        // static evaluation is too far below alpha for any margin to make up the difference it
+        // isn't worth searching. Not when alpha is a mate score, which no margin relates to.
+        if ply > 0
+            && i > 1
+            && depth <= FUTILITY_MAX_DEPTH
+            && !is_mate_score(alpha)
+            && static_eval + futility_margin(depth, improving) <= alpha
+            && is_quiet(&mv)
+            && !in_check
+            && mv.meta & MOVE_CHECK == 0
+        {
+            continue;
+        }
+        if ply == 0 && !XBOARD_MODE.load(Ordering::Relaxed) && !QUIET.load(Ordering::Relaxed) {
+            send!("info currmove {} currmovenumber {i}", mv.to_string());
         }
         let new_board = board.make_move(&mv);
-        let (pv, score, mate_in, child_nodes) = search(new_board, -beta, -alpha, depth + 1);
-        nodes += child_nodes;
+        let nodes_before = info.nodes;
+        info.push_move(&board, &new_board, mv);
+        info.keys.push(key);
+        // Check extensions: checks are searched a ply deeper, as they force the reply and often
+        // lead somewhere. The extensions along a line are capped at the iteration's depth (and at
+        // MAX_EXTENSIONS), so that long checking sequences can't blow up the search.
+        let max_extensions = cmp::min(info.iteration, MAX_EXTENSIONS);
+        let extension = (mv.meta & MOVE_CHECK > 0 && info.extensions < max_extensions) as u8;
+        info.extensions += extension;
+        let child_depth = depth - 1 + extension;
+        // Late move reductions: quiet moves ordered late are unlikely to be any good, so they are
+        // first searched less deeply, and only searched to the full depth if they beat alpha.
+        // Checks, and moves out of check, are left alone; positions that aren't improving are
+        // reduced a ply more.
+        let reduction = if i > 3
+            && depth >= 3
+            && is_quiet(&mv)
+            && !in_check
+            && mv.meta & MOVE_CHECK == 0
+            && !info.killers[ply as usize].contains(&Some(mv))
+        {
+            (info.reduction(depth, i) + !improving as u8).min(depth - 2)
+        } else {
+            0
+        };
+        // Principal variation search: with good move ordering the first move is usually best, so
+        // the others are only searched with a null window to prove that they're no better. The
+        // rare move that turns out better is searched again with the full window for its score.
+        let mut score = if i == 1 {
+            search_node(new_board, -beta, -alpha, ply + 1, child_depth, info)
+        } else {
+            let reduced_depth = child_depth - reduction;
+            search_node(new_board, -alpha - 1, -alpha, ply + 1, reduced_depth, info)
+        };
+        if reduction > 0 && -score > alpha && !info.stopped() {
+            score = search_node(new_board, -alpha - 1, -alpha, ply + 1, child_depth, info);
+        }
+        if i > 1 && -score > alpha && -score < beta && !info.stopped() {
+            score = search_node(new_board, -beta, -alpha, ply + 1, child_depth, info);
+        }
+        info.extensions -= extension;
+        info.keys.pop();
+        info.pop_move();
+        // The child's result is meaningless if the search was aborted underneath it; keep
+        // whatever we had from the moves that were fully searched.
+        if info.stopped() {
+            break;
+        }
+        // Moves that fail low only have an upper bound for a score, which is alpha: they tie with
+        // the best move so far, but stay behind it, as the sort is stable.
+        if ply == 0 {
+            info.root_moves[info.pv_index + i - 1].nodes += info.nodes - nodes_before;
+            info.root_moves[info.pv_index + i - 1].score = -score;
+        }
 
         if -score >= beta {
-            return (
-                mv.to_string() + " " + &pv,
-                beta,
-                match best_mate_in {
-                    None => None,
-                    Some(m) => Some(m + 1),
-                },
-                nodes,
+            if storable {
+                tt::store(tt::Entry {
+                    key,
+                    depth,
+                    score: score_to_tt(beta, ply),
+                    bound: Bound::Lower,
+                    best_move: Some(mv),
+                    static_eval: raw_eval,
+                });
+            }
+            if is_quiet(&mv) {
+                if storable && !in_check {
+                    info.update_correction(&board, depth, static_eval, beta, Bound::Lower);
+                }
+                info.store_killer(ply, mv);
+                info.update_history(&board, &mv, depth);
+            } else if mv.meta & MOVE_CAPTURE > 0 {
+                info.update_capture_history(&board, &mv, depth);
+            }
+            info.update_pv(ply, mv);
+            return beta;
+        }
+        if ply == 0 && -score <= alpha && SHOW_REFUTATIONS.load(Ordering::Relaxed) {
+            let refutation: Vec<String> = info.pv[ply as usize + 1]
+                .iter()
+                .map(|mv| mv.to_string())
+                .collect();
+            send!(
+                "info refutation {} {}",
+                mv.to_string(),
+                refutation.join(" ")
             );
         }
         if -score > alpha {
             alpha = -score;
-            best_mate_in = mate_in;
-            best_pv = mv.to_string() + " " + &pv.to_string();
-            if depth == 0 {
-                print_info(-score, mate_in, nodes, &best_pv);
-            }
-        } else if score == -CHECKMATE &&
-                let Some(bm) = best_mate_in &&
-                let Some(m) = mate_in &&
-                m < bm {
-            println!("{}{} Found a better CHECKMATE best_mate_in {best_mate_in:?}  mate_in: {m}  score: {score} {:?}",
-            "  ".repeat(depth as usize), if board.white_to_move() { "W" } else {"B"}, mate_in);
-            let mut buffer = String::new();
-            io::stdin().read_line(&mut buffer).ok();
-            alpha = -score;
-            best_mate_in = mate_in;
-            best_pv = mv.to_string() + " " + &pv.to_string();
-            if depth == 0 {
-                print_info(-score, mate_in, nodes, &best_pv);
-            }
+            best_move = Some(mv);
+            info.update_pv(ply, mv);
+        }
+    }
+    if i == 0 {
+        if in_check {
+            return -CHECKMATE + ply as i64;
+        }
+        return info.draw_score(ply);
+    }
+    // A stopped search's score is only a guess.
+    if storable && !info.stopped() {
+        let bound = if alpha > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        // Captures and promotions change the material, which the evaluation gets right: what a
+        // line starting with one finds says nothing about the evaluation of the structure.
+        if !in_check && best_move.is_none_or(|mv| is_quiet(&mv)) {
+            info.update_correction(&board, depth, static_eval, alpha, bound);
         }
+        tt::store(tt::Entry {
+            key,
+            depth,
+            score: score_to_tt(alpha, ply),
+            bound,
+            best_move,
+            static_eval: raw_eval,
+        });
     }
-    (
-        best_pv,
-        alpha,
-        match best_mate_in {
-            None => None,
-            Some(m) => Some(m + 1),
-        },
-        nodes,
-    )
+    alpha
 }
@@ -0,0 +1,1658 @@
+// Static evaluation: the PeSTO material and piece-square tables, plus terms for what the tables
+// can't see. Every term is scored separately for the middlegame and the endgame, and the two are
+// interpolated by the game phase (tapered evaluation).
+use super::arrayboard::{is_piece_white, piece_type, ArrayBoard, PieceType};
+use super::kpk;
+use super::nnue;
+use std::cell::RefCell;
+use std::cmp;
+use std::fs;
+use std::sync::OnceLock;
+
+// PeSTO piece evaluation tables
+#[rustfmt::skip]
+const MG_PAWN_TABLE: [i16;64] = [
+    0,   0,   0,   0,   0,   0,  0,   0,
+    98, 134,  61,  95,  68, 126, 34, -11,
+    -6,   7,  26,  31,  65,  56, 25, -20,
+    -14,  13,   6,  21,  23,  12, 17, -23,
+    -27,  -2,  -5,  12,  17,   6, 10, -25,
+    -26,  -4,  -4, -10,   3,   3, 33, -12,
+    -35,  -1, -20, -23, -15,  24, 38, -22,
+    0,   0,   0,   0,   0,   0,  0,   0,
+];
+
+#[rustfmt::skip]
+const EG_PAWN_TABLE: [i16;64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+    178, 173, 158, 134, 147, 132, 165, 187,
+     94, 100,  85,  67,  56,  53,  82,  84,
+     32,  24,  13,   5,  -2,   4,  17,  17,
+     13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+      4,   7,  -6,   1,   0,  -5,  -1,  -8,
+     13,   8,   8,  10,  13,   0,   2,  -7,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const MG_KNIGHT_TABLE: [i16;64] = [
+    0,   0,   0,   0,   0,   0,   0,   0,
+    178, 173, 158, 134, 147, 132, 165, 187,
+    94, 100,  85,  67,  56,  53,  82,  84,
+    32,  24,  13,   5,  -2,   4,  17,  17,
+    13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+    4,   7,  -6,   1,   0,  -5,  -1,  -8,
+    13,   8,   8,  10,  13,   0,   2,  -7,
+    0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const EG_KNIGHT_TABLE: [i16;64] = [
+    -58, -38, -13, -28, -31, -27, -63, -99,
+    -25,  -8, -25,  -2,  -9, -25, -24, -52,
+    -24, -20,  10,   9,  -1,  -9, -19, -41,
+    -17,   3,  22,  22,  22,  11,   8, -18,
+    -18,  -6,  16,  25,  16,  17,   4, -18,
+    -23,  -3,  -1,  15,  10,  -3, -20, -22,
+    -42, -20, -10,  -5,  -2, -20, -23, -44,
+    -29, -51, -23, -15, -22, -18, -50, -64,
+];
+
+#[rustfmt::skip]
+const MG_BISHOP_TABLE: [i16;64] = [
+    -29,   4, -82, -37, -25, -42,   7,  -8,
+    -26,  16, -18, -13,  30,  59,  18, -47,
+    -16,  37,  43,  40,  35,  50,  37,  -2,
+     -4,   5,  19,  50,  37,  37,   7,  -2,
+     -6,  13,  13,  26,  34,  12,  10,   4,
+      0,  15,  15,  15,  14,  27,  18,  10,
+      4,  15,  16,   0,   7,  21,  33,   1,
+    -33,  -3, -14, -21, -13, -12, -39, -21,
+];
+
+#[rustfmt::skip]
+const EG_BISHOP_TABLE: [i16;64] = [
+    -14, -21, -11,  -8, -7,  -9, -17, -24,
+     -8,  -4,   7, -12, -3, -13,  -4, -14,
+      2,  -8,   0,  -1, -2,   6,   0,   4,
+     -3,   9,  12,   9, 14,  10,   3,   2,
+     -6,   3,  13,  19,  7,  10,  -3,  -9,
+    -12,  -3,   8,  10, 13,   3,  -7, -15,
+    -14, -18,  -7,  -1,  4,  -9, -15, -27,
+    -23,  -9, -23,  -5, -9, -16,  -5, -17,
+];
+
+#[rustfmt::skip]
+const MG_ROOK_TABLE: [i16;64] = [
+     32,  42,  32,  51, 63,  9,  31,  43,
+     27,  32,  58,  62, 80, 67,  26,  44,
+     -5,  19,  26,  36, 17, 45,  61,  16,
+    -24, -11,   7,  26, 24, 35,  -8, -20,
+    -36, -26, -12,  -1,  9, -7,   6, -23,
+    -45, -25, -16, -17,  3,  0,  -5, -33,
+    -44, -16, -20,  -9, -1, 11,  -6, -71,
+    -19, -13,   1,  17, 16,  7, -37, -26,
+];
+
+#[rustfmt::skip]
+const EG_ROOK_TABLE: [i16;64] = [
+    13, 10, 18, 15, 12,  12,   8,   5,
+    11, 13, 13, 11, -3,   3,   8,   3,
+     7,  7,  7,  5,  4,  -3,  -5,  -3,
+     4,  3, 13,  1,  2,   1,  -1,   2,
+     3,  5,  8,  4, -5,  -6,  -8, -11,
+    -4,  0, -5, -1, -7, -12,  -8, -16,
+    -6, -6,  0,  2, -9,  -9, -11,  -3,
+    -9,  2,  3, -1, -5, -13,   4, -20,
+];
+
+#[rustfmt::skip]
+const MG_QUEEN_TABLE: [i16;64] = [
+    -28,   0,  29,  12,  59,  44,  43,  45,
+    -24, -39,  -5,   1, -16,  57,  28,  54,
+    -13, -17,   7,   8,  29,  56,  47,  57,
+    -27, -27, -16, -16,  -1,  17,  -2,   1,
+     -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+    -14,   2, -11,  -2,  -5,   2,  14,   5,
+    -35,  -8,  11,   2,   8,  15,  -3,   1,
+     -1, -18,  -9,  10, -15, -25, -31, -50,
+];
+
+#[rustfmt::skip]
+const EG_QUEEN_TABLE: [i16;64] = [
+     -9,  22,  22,  27,  27,  19,  10,  20,
+    -17,  20,  32,  41,  58,  25,  30,   0,
+    -20,   6,   9,  49,  47,  35,  19,   9,
+      3,  22,  24,  45,  57,  40,  57,  36,
+    -18,  28,  19,  47,  31,  34,  39,  23,
+    -16, -27,  15,   6,   9,  17,  10,   5,
+    -22, -23, -30, -16, -16, -23, -36, -32,
+    -33, -28, -22, -43,  -5, -32, -20, -41,
+];
+
+#[rustfmt::skip]
+const MG_KING_TABLE: [i16;64] = [
+    -65,  23,  16, -15, -56, -34,   2,  13,
+     29,  -1, -20,  -7,  -8,  -4, -38, -29,
+     -9,  24,   2, -16, -20,   6,  22, -22,
+    -17, -20, -12, -27, -30, -25, -14, -36,
+    -49,  -1, -27, -39, -46, -44, -33, -51,
+    -14, -14, -22, -46, -44, -30, -15, -27,
+      1,   7,  -8, -64, -43, -16,   9,   8,
+    -15,  36,  12, -54,   8, -28,  24,  14,
+];
+
+#[rustfmt::skip]
+const EG_KING_TABLE: [i16;64] = [
+    -74, -35, -18, -18, -11,  15,   4, -17,
+    -12,  17,  14,  17,  17,  38,  23,  11,
+     10,  17,  23,  15,  20,  45,  44,  13,
+     -8,  22,  24,  27,  26,  33,  26,   3,
+    -18,  -4,  21,  24,  27,  23,   9, -11,
+    -19,  -3,  11,  21,  23,  16,   7,  -9,
+    -27, -11,   4,  13,  14,   4,  -5, -17,
+    -53, -34, -21, -11, -28, -14, -24, -43
+];
+
+const MG_PIECE_VALUES: [i16; 6] = [
+    82,   // Pawns
+    337,  // Knights
+    365,  // Bishops
+    477,  // Rooks
+    1025, // Queens
+    0,    // Kings
+];
+const EG_PIECE_VALUES: [i16; 6] = [
+    94,  // Pawns
+    281, // Knights
+    297, // Bishops
+    512, // Rooks
+    936, // Queens
+    0,   // Kings
+];
+
+const MG_PESTO: [[i16; 64]; 6] = [
+    MG_PAWN_TABLE,
+    MG_KNIGHT_TABLE,
+    MG_BISHOP_TABLE,
+    MG_ROOK_TABLE,
+    MG_QUEEN_TABLE,
+    MG_KING_TABLE,
+];
+const EG_PESTO: [[i16; 64]; 6] = [
+    EG_PAWN_TABLE,
+    EG_KNIGHT_TABLE,
+    EG_BISHOP_TABLE,
+    EG_ROOK_TABLE,
+    EG_QUEEN_TABLE,
+    EG_KING_TABLE,
+];
+
+// Game phase: what each piece, pawn to king, counts towards it. The phase is 24 with all the
+// pieces on the board (with these weights) and 0 with none.
+const PHASE_WEIGHTS: [i64; 6] = [0, 1, 1, 2, 4, 0];
+// The middlegame score's share of the evaluation, out of TAPER_SCALE, at seven evenly spaced
+// phases from no pieces to all of them; phases in between are interpolated. The default is a
+// straight line, the classic taper.
+const TAPER_SCALE: i64 = 240;
+const TAPER_CURVE: [i64; 7] = [0, 40, 80, 120, 160, 200, 240];
+
+// Every weight of the evaluation, which start as the values compiled in but can be replaced by
+// tuned ones from a file at startup (see load_weights). Each is named after its compiled default,
+// in lower case.
+pub struct Weights {
+    mg_piece_values: [i64; 6],
+    eg_piece_values: [i64; 6],
+    mg_tables: [[i64; 64]; 6],
+    eg_tables: [[i64; 64]; 6],
+    // Piece values and piece-square tables combined, by piece bits less 2 and square, which is how
+    // the board keeps its running scores.
+    pub mg_table: [[i32; 64]; 12],
+    pub eg_table: [[i32; 64]; 12],
+    phase_weights: [i64; 6],
+    taper_curve: [i64; 7],
+    // Phase weights by piece bits less 2, for the board's running phase, and the phase of the
+    // starting position's pieces.
+    pub phase_table: [i32; 12],
+    phase_total: i64,
+    doubled_pawn: [i64; 2],
+    isolated_pawn: [i64; 2],
+    backward_pawn: [i64; 2],
+    passed_pawn: [[i64; 8]; 2],
+    connected_passer: [i64; 2],
+    unstoppable_passer: [i64; 2],
+    shield_pawn: [i64; 2],
+    shield_pawn_advanced: [i64; 2],
+    shield_missing: [i64; 2],
+    king_attack_weight: [i64; 4],
+    king_attackers_scale: [i64; 8],
+    rook_open_file: [i64; 2],
+    rook_semi_open_file: [i64; 2],
+    rook_on_seventh: [i64; 2],
+    threat_by_lesser: [[i64; 2]; 4],
+    hanging: [i64; 2],
+    pawn_push_threat: [i64; 2],
+    space: [i64; 2],
+    tropism: [[i64; 2]; 4],
+    tempo: [i64; 2],
+    bishop_pair: [i64; 2],
+    knight_pawns: [i64; 2],
+    rook_pawns: [i64; 2],
+    rook_redundancy: [i64; 2],
+    queen_rook_redundancy: [i64; 2],
+    two_minors_vs_rook: [i64; 2],
+    minor_vs_pawns: [i64; 2],
+    mop_up_edge: [i64; 2],
+    mop_up_kings: [i64; 2],
+    mop_up_corner: [i64; 2],
+    drawish_scale: [i64; 5],
+}
+
+const PIECE_NAMES: [&str; 6] = ["pawn", "knight", "bishop", "rook", "queen", "king"];
+
+impl Default for Weights {
+    fn default() -> Weights {
+        let mut weights = Weights {
+            mg_piece_values: MG_PIECE_VALUES.map(i64::from),
+            eg_piece_values: EG_PIECE_VALUES.map(i64::from),
+            mg_tables: MG_PESTO.map(|table| table.map(i64::from)),
+            eg_tables: EG_PESTO.map(|table| table.map(i64::from)),
+            mg_table: [[0; 64]; 12],
+            eg_table: [[0; 64]; 12],
+            phase_weights: PHASE_WEIGHTS,
+            taper_curve: TAPER_CURVE,
+            phase_table: [0; 12],
+            phase_total: 0,
+            doubled_pawn: DOUBLED_PAWN,
+            isolated_pawn: ISOLATED_PAWN,
+            backward_pawn: BACKWARD_PAWN,
+            passed_pawn: PASSED_PAWN,
+            connected_passer: CONNECTED_PASSER,
+            unstoppable_passer: UNSTOPPABLE_PASSER,
+            shield_pawn: SHIELD_PAWN,
+            shield_pawn_advanced: SHIELD_PAWN_ADVANCED,
+            shield_missing: SHIELD_MISSING,
+            king_attack_weight: KING_ATTACK_WEIGHT,
+            king_attackers_scale: KING_ATTACKERS_SCALE,
+            rook_open_file: ROOK_OPEN_FILE,
+            rook_semi_open_file: ROOK_SEMI_OPEN_FILE,
+            rook_on_seventh: ROOK_ON_SEVENTH,
+            threat_by_lesser: THREAT_BY_LESSER,
+            hanging: HANGING,
+            pawn_push_threat: PAWN_PUSH_THREAT,
+            space: SPACE,
+            tropism: TROPISM,
+            tempo: TEMPO,
+            bishop_pair: BISHOP_PAIR,
+            knight_pawns: KNIGHT_PAWNS,
+            rook_pawns: ROOK_PAWNS,
+            rook_redundancy: ROOK_REDUNDANCY,
+            queen_rook_redundancy: QUEEN_ROOK_REDUNDANCY,
+            two_minors_vs_rook: TWO_MINORS_VS_ROOK,
+            minor_vs_pawns: MINOR_VS_PAWNS,
+            mop_up_edge: MOP_UP_EDGE,
+            mop_up_kings: MOP_UP_KINGS,
+            mop_up_corner: MOP_UP_CORNER,
+            drawish_scale: DRAWISH_SCALE,
+        };
+        weights.combine_tables();
+        weights
+    }
+}
+
+impl Weights {
+    // Rebuilds mg_table and eg_table from the piece values and piece-square tables, and the phase
+    // table and total from the phase weights. The tables are from white's point of view, so
+    // black's are flipped.
+    fn combine_tables(&mut self) {
+        for piece in 0..6 {
+            self.phase_table[piece * 2] = self.phase_weights[piece] as i32;
+            self.phase_table[piece * 2 + 1] = self.phase_weights[piece] as i32;
+        }
+        let [_, knight, bishop, rook, queen, _] = self.phase_weights;
+        self.phase_total = cmp::max(4 * (knight + bishop + rook) + 2 * queen, 1);
+        for piece in 0..6 {
+            for sq in 0..64 {
+                for (table, values, tables) in [
+                    (&mut self.mg_table, &self.mg_piece_values, &self.mg_tables),
+                    (&mut self.eg_table, &self.eg_piece_values, &self.eg_tables),
+                ] {
+                    table[piece * 2 + 1][sq] = (values[piece] + tables[piece][sq]) as i32;
+                    table[piece * 2][sq] = (values[piece] + tables[piece][sq ^ 0b111000]) as i32;
+                }
+            }
+        }
+    }
+
+    // The weights a file may set, by name, as flat lists of numbers.
+    fn by_name(&mut self) -> Vec<(String, &mut [i64])> {
+        let mut fields: Vec<(String, &mut [i64])> = vec![
+            ("mg_piece_values".to_string(), &mut self.mg_piece_values),
+            ("eg_piece_values".to_string(), &mut self.eg_piece_values),
+        ];
+        for (stage, tables) in [("mg", &mut self.mg_tables), ("eg", &mut self.eg_tables)] {
+            for (piece, table) in PIECE_NAMES.iter().zip(tables.iter_mut()) {
+                fields.push((format!("{stage}_{piece}_table"), table));
+            }
+        }
+        fields.extend([
+            ("doubled_pawn".to_string(), &mut self.doubled_pawn[..]),
+            ("isolated_pawn".to_string(), &mut self.isolated_pawn),
+            ("backward_pawn".to_string(), &mut self.backward_pawn),
+            (
+                "passed_pawn".to_string(),
+                self.passed_pawn.as_flattened_mut(),
+            ),
+            ("connected_passer".to_string(), &mut self.connected_passer),
+            (
+                "unstoppable_passer".to_string(),
+                &mut self.unstoppable_passer,
+            ),
+            ("shield_pawn".to_string(), &mut self.shield_pawn),
+            (
+                "shield_pawn_advanced".to_string(),
+                &mut self.shield_pawn_advanced,
+            ),
+            ("shield_missing".to_string(), &mut self.shield_missing),
+            (
+                "king_attack_weight".to_string(),
+                &mut self.king_attack_weight,
+            ),
+            (
+                "king_attackers_scale".to_string(),
+                &mut self.king_attackers_scale,
+            ),
+            ("rook_open_file".to_string(), &mut self.rook_open_file),
+            (
+                "rook_semi_open_file".to_string(),
+                &mut self.rook_semi_open_file,
+            ),
+            ("rook_on_seventh".to_string(), &mut self.rook_on_seventh),
+            (
+                "threat_by_lesser".to_string(),
+                self.threat_by_lesser.as_flattened_mut(),
+            ),
+            ("hanging".to_string(), &mut self.hanging),
+            ("pawn_push_threat".to_string(), &mut self.pawn_push_threat),
+            ("space".to_string(), &mut self.space),
+            ("tropism".to_string(), self.tropism.as_flattened_mut()),
+            ("tempo".to_string(), &mut self.tempo),
+            ("bishop_pair".to_string(), &mut self.bishop_pair),
+            ("knight_pawns".to_string(), &mut self.knight_pawns),
+            ("rook_pawns".to_string(), &mut self.rook_pawns),
+            ("rook_redundancy".to_string(), &mut self.rook_redundancy),
+            (
+                "queen_rook_redundancy".to_string(),
+                &mut self.queen_rook_redundancy,
+            ),
+            (
+                "two_minors_vs_rook".to_string(),
+                &mut self.two_minors_vs_rook,
+            ),
+            ("minor_vs_pawns".to_string(), &mut self.minor_vs_pawns),
+            ("mop_up_edge".to_string(), &mut self.mop_up_edge),
+            ("mop_up_kings".to_string(), &mut self.mop_up_kings),
+            ("mop_up_corner".to_string(), &mut self.mop_up_corner),
+            ("drawish_scale".to_string(), &mut self.drawish_scale),
+            ("phase_weights".to_string(), &mut self.phase_weights),
+            ("taper_curve".to_string(), &mut self.taper_curve),
+        ]);
+        fields
+    }
+
+    // Sets the weights named in `text`, leaving the rest alone. The format is a small subset of
+    // TOML: `name = value` lines, where a value is a number or a list of them in brackets, lists of
+    // lists (tables by stage, say) are flattened, and `#` starts a comment. A JSON object of the
+    // same names and values reads the same way. Nothing is set unless all of `text` reads.
+    fn parse(&mut self, text: &str) -> Result<(), String> {
+        let mut spaced = String::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            for c in line.chars() {
+                match c {
+                    '[' | ']' | '=' => spaced.extend([' ', c, ' ']),
+                    ':' => spaced.push_str(" = "),
+                    ',' | '{' | '}' | '"' => spaced.push(' '),
+                    c => spaced.push(c),
+                }
+            }
+            spaced.push(' ');
+        }
+        let mut tokens = spaced.split_whitespace().peekable();
+        let mut fields = self.by_name();
+        let mut parsed = Vec::new();
+        while let Some(name) = tokens.next() {
+            if tokens.next() != Some("=") {
+                return Err(format!("expected '=' after {name}"));
+            }
+            let mut values = Vec::new();
+            let mut depth = 0;
+            for token in tokens.by_ref() {
+                match token {
+                    "[" => depth += 1,
+                    "]" => depth -= 1,
+                    number => values.push(
+                        number
+                            .parse::<i64>()
+                            .map_err(|_| format!("bad number '{number}' for {name}"))?,
+                    ),
+                }
+                if depth <= 0 {
+                    break;
+                }
+            }
+            if depth > 0 {
+                return Err(format!("unclosed list for {name}"));
+            }
+            let Some(field) = fields.iter().position(|(field, _)| field == name) else {
+                return Err(format!("unknown weight {name}"));
+            };
+            if values.len() != fields[field].1.len() {
+                return Err(format!(
+                    "{name} needs {} values, found {}",
+                    fields[field].1.len(),
+                    values.len()
+                ));
+            }
+            parsed.push((field, values));
+        }
+        for (field, values) in parsed {
+            fields[field].1.copy_from_slice(&values);
+        }
+        self.combine_tables();
+        Ok(())
+    }
+}
+
+static WEIGHTS: OnceLock<Weights> = OnceLock::new();
+
+// The weights in use: the compiled ones unless load_weights set others first.
+pub fn weights() -> &'static Weights {
+    WEIGHTS.get_or_init(Weights::default)
+}
+
+// Replaces the compiled weights with those set in the file at `path`. Only possible before the
+// first evaluation, as boards keep running scores made with the weights of the time.
+pub fn load_weights(path: &str) -> Result<(), String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("cannot read {path}: {e}"))?;
+    let mut weights = Weights::default();
+    weights.parse(&text).map_err(|e| format!("{path}: {e}"))?;
+    WEIGHTS
+        .set(weights)
+        .map_err(|_| String::from("weights already in use"))
+}
+
+// A term's value per side ([white, black]) and game stage ([middlegame, endgame]), from each
+// side's own point of view.
+pub type Term = [[i64; 2]; 2];
+
+// Pawn structure weights, [middlegame, endgame], per pawn. Doubled pawns are counted once for each
+// pawn on the file after the first.
+const DOUBLED_PAWN: [i64; 2] = [-10, -25];
+const ISOLATED_PAWN: [i64; 2] = [-10, -15];
+const BACKWARD_PAWN: [i64; 2] = [-8, -10];
+
+// Where each side's pawns are, as a set of squares by side. Small enough to keep in the pawn
+// hash table, so only worked out when the pawn structure isn't there.
+#[derive(Copy, Clone)]
+struct Pawns {
+    on: [u64; 2],
+}
+
+// The squares of the a-file.
+const FILE_A: u64 = 0x0101_0101_0101_0101;
+
+impl Pawns {
+    fn new(board: &ArrayBoard) -> Pawns {
+        let mut pawns = Pawns { on: [0; 2] };
+        for sq in 0..64 {
+            let piece = board.get_piece(sq);
+            if piece_type(piece) == PieceType::Pawn as u32 {
+                pawns.on[!is_piece_white(piece) as usize] |= 1 << sq;
+            }
+        }
+        pawns
+    }
+
+    fn at(&self, side: usize, row: i64, file: i64) -> bool {
+        (0..8).contains(&row)
+            && (0..8).contains(&file)
+            && self.on[side] >> (row * 8 + file) & 1 != 0
+    }
+
+    // How many of the side's pawns are on `file`.
+    fn on_file(&self, side: usize, file: usize) -> i64 {
+        (self.on[side] & FILE_A << file).count_ones() as i64
+    }
+
+    // Squares of the side's pawns, with their rows and files. Rows count from the eighth rank, so
+    // white pawns advance to lower rows.
+    fn squares(&self, side: usize) -> impl Iterator<Item = (usize, i64, i64)> + '_ {
+        (0..64)
+            .filter(move |&sq| self.on[side] >> sq & 1 != 0)
+            .map(|sq| (sq, (sq / 8) as i64, (sq % 8) as i64))
+    }
+}
+
+// The row a side's pawns advance by.
+fn forward(side: usize) -> i64 {
+    if side == 0 {
+        -1
+    } else {
+        1
+    }
+}
+
+fn add(term: &mut Term, side: usize, weight: [i64; 2], count: i64) {
+    term[side][0] += weight[0] * count;
+    term[side][1] += weight[1] * count;
+}
+
+// Pawn weaknesses: doubled pawns, isolated pawns (no friendly pawns on the files next to them) and
+// backward pawns (all the friendly pawns on the files next to them have gone past, so none can
+// ever defend them, and an enemy pawn guards the square in front of them).
+fn pawn_structure(pawns: &Pawns) -> Term {
+    let w = weights();
+    let mut term = [[0; 2]; 2];
+    for side in 0..2 {
+        for file in 0..8 {
+            add(
+                &mut term,
+                side,
+                w.doubled_pawn,
+                cmp::max(pawns.on_file(side, file) - 1, 0),
+            );
+        }
+        let forward = forward(side);
+        for (_, row, file) in pawns.squares(side) {
+            let neighbours = [file - 1, file + 1];
+            if neighbours
+                .iter()
+                .all(|&f| !(0..8).contains(&f) || pawns.on_file(side, f as usize) == 0)
+            {
+                add(&mut term, side, w.isolated_pawn, 1);
+                continue;
+            }
+            let supportable = (0..8).any(|r| {
+                (r - row) * forward <= 0 && neighbours.iter().any(|&f| pawns.at(side, r, f))
+            });
+            let stop_guarded = neighbours
+                .iter()
+                .any(|&f| pawns.at(1 - side, row + 2 * forward, f));
+            if !supportable && stop_guarded {
+                add(&mut term, side, w.backward_pawn, 1);
+            }
+        }
+    }
+    term
+}
+
+// Passed pawn bonuses by how far the pawn has advanced, from its own first rank, [middlegame,
+// endgame]. A passer is worth far more in the endgame, with fewer pieces left to stop it.
+const PASSED_PAWN: [[i64; 8]; 2] = [
+    [0, 5, 5, 10, 20, 35, 60, 0],
+    [0, 10, 15, 25, 45, 75, 120, 0],
+];
+// For a passer with a friendly pawn beside it or defending it.
+const CONNECTED_PASSER: [i64; 2] = [5, 15];
+
+// Passed pawns: pawns that no enemy pawn can stop, as none are ahead of them on their own file or
+// the files next to them. As squares, by side.
+fn passers(pawns: &Pawns) -> [u64; 2] {
+    let mut passers = [0; 2];
+    for (side, side_passers) in passers.iter_mut().enumerate() {
+        let forward = forward(side);
+        for (sq, row, file) in pawns.squares(side) {
+            let files = [file - 1, file, file + 1];
+            let stoppable = (0..8).any(|r| {
+                (r - row) * forward > 0 && files.iter().any(|&f| pawns.at(1 - side, r, f))
+            });
+            if !stoppable {
+                *side_passers |= 1 << sq;
+            }
+        }
+    }
+    passers
+}
+
+// Passed pawn bonuses. A passer whose stop square (the one in front of it) is taken by a piece
+// gets half its bonus, and one whose stop square the enemy only attacks three quarters.
+fn passed_pawns(board: &ArrayBoard, pawns: &Pawns, passers: [u64; 2]) -> Term {
+    let w = weights();
+    let mut term = [[0; 2]; 2];
+    for side in 0..2 {
+        let forward = forward(side);
+        for (sq, row, file) in pawns.squares(side) {
+            if passers[side] & (1 << sq) == 0 {
+                continue;
+            }
+            let rank = if side == 0 { 7 - row } else { row } as usize;
+            let stop = (sq as i64 + 8 * forward) as usize;
+            let (numerator, denominator) = if board.get_piece(stop) != 0 {
+                (1, 2)
+            } else if board.is_square_attacked(stop as u32, side == 1) {
+                (3, 4)
+            } else {
+                (1, 1)
+            };
+            for (stage, value) in term[side].iter_mut().enumerate() {
+                *value += w.passed_pawn[stage][rank] * numerator / denominator;
+            }
+            let connected = [file - 1, file + 1]
+                .iter()
+                .any(|&f| pawns.at(side, row, f) || pawns.at(side, row - forward, f));
+            if connected {
+                add(&mut term, side, w.connected_passer, 1);
+            }
+        }
+    }
+    term
+}
+
+// For an unstoppable passer, [middlegame, endgame]: as good as a queen, less the pawn it was.
+const UNSTOPPABLE_PASSER: [i64; 2] = [0, 700];
+
+// Unstoppable passers: against a side without pieces, a passer whose path is clear and that its
+// king can't catch (it is outside the king's "square") promotes. When both sides have one, it's a
+// race, and only the side that promotes first gets the bonus. Checks and stalemates along the way
+// are left to the search.
+fn unstoppable_passers(
+    board: &ArrayBoard,
+    pawns: &Pawns,
+    material: &Material,
+    passers: [u64; 2],
+) -> Term {
+    let w = weights();
+    let mut term = [[0; 2]; 2];
+    if passers == [0; 2] {
+        return term;
+    }
+    let kings = king_squares(board);
+    // Per side, the fewest plies to promote one, counting from the side to move's move.
+    let mut plies = [None; 2];
+    for side in 0..2 {
+        if material.pieces[1 - side][1..5] != [0; 4] {
+            continue;
+        }
+        let to_move = board.white_to_move() == (side == 0);
+        let forward = forward(side);
+        for (sq, row, file) in pawns.squares(side) {
+            if passers[side] & (1 << sq) == 0 {
+                continue;
+            }
+            let promotion_row = if side == 0 { 0 } else { 7 };
+            let path_clear = (1..=(promotion_row - row).abs())
+                .all(|n| board.get_piece(((row + n * forward) * 8 + file) as usize) == 0);
+            if !path_clear {
+                continue;
+            }
+            // A pawn on its second rank can move two squares at once.
+            let moves = cmp::min((promotion_row - row).abs(), 5);
+            let king = kings[1 - side];
+            let king_distance = cmp::max(
+                (king / 8).abs_diff(promotion_row as usize),
+                (king % 8).abs_diff(file as usize),
+            ) as i64;
+            if king_distance - (!to_move as i64) <= moves {
+                continue;
+            }
+            let side_plies = 2 * moves - to_move as i64;
+            plies[side] = Some(plies[side].map_or(side_plies, |p: i64| cmp::min(p, side_plies)));
+        }
+    }
+    for side in 0..2 {
+        let Some(ours) = plies[side] else {
+            continue;
+        };
+        if plies[1 - side].is_none_or(|theirs| ours < theirs) {
+            add(&mut term, side, w.unstoppable_passer, 1);
+        }
+    }
+    term
+}
+
+// Pawn hash table: what can be worked out from the pawns alone, by pawn key. The pawn structure
+// changes far less often than the position, so most evaluations find it here. Each thread has its
+// own, so that it needs no locking.
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+#[derive(Copy, Clone)]
+struct PawnEntry {
+    key: u64,
+    pawns: Pawns,
+    structure: Term,
+    passers: [u64; 2],
+}
+
+thread_local! {
+    static PAWN_TABLE: RefCell<Vec<Option<PawnEntry>>> =
+        RefCell::new(vec![None; PAWN_TABLE_SIZE]);
+}
+
+fn pawn_entry(board: &ArrayBoard) -> PawnEntry {
+    let key = board.pawn_key();
+    PAWN_TABLE.with(|table| {
+        let slot = &mut table.borrow_mut()[(key % PAWN_TABLE_SIZE as u64) as usize];
+        match *slot {
+            Some(entry) if entry.key == key => entry,
+            _ => {
+                let pawns = Pawns::new(board);
+                let entry = PawnEntry {
+                    key,
+                    pawns,
+                    structure: pawn_structure(&pawns),
+                    passers: passers(&pawns),
+                };
+                *slot = Some(entry);
+                entry
+            }
+        }
+    })
+}
+
+// King shelter, by the friendly pawns on the king's file and the files next to it, [middlegame,
+// endgame]: for a pawn one square ahead of the king, for one two squares ahead, and for a file with
+// neither. A shield matters little once the queens are off, so these are middlegame weights.
+const SHIELD_PAWN: [i64; 2] = [12, 0];
+const SHIELD_PAWN_ADVANCED: [i64; 2] = [6, 0];
+const SHIELD_MISSING: [i64; 2] = [-15, 0];
+// Weights of a knight, bishop, rook and queen's attacks, per square of the enemy king zone (the
+// king's square and those next to it) they attack.
+const KING_ATTACK_WEIGHT: [i64; 4] = [20, 20, 40, 80];
+// The percentage of the attack weight that counts, by the number of pieces attacking the zone. A
+// lone attacker is rarely dangerous; several together are.
+const KING_ATTACKERS_SCALE: [i64; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+
+// Where the kings are, [white, black].
+fn king_squares(board: &ArrayBoard) -> [usize; 2] {
+    let mut kings = [0; 2];
+    for sq in 0..64 {
+        let piece = board.get_piece(sq);
+        if piece_type(piece) == PieceType::King as u32 {
+            kings[!is_piece_white(piece) as usize] = sq;
+        }
+    }
+    kings
+}
+
+// King safety: the pawn shield in front of each king, and how hard the enemy pieces attack the
+// squares around it.
+fn king_safety(board: &ArrayBoard, pawns: &Pawns) -> Term {
+    let w = weights();
+    let mut term = [[0; 2]; 2];
+    let kings = king_squares(board);
+    for side in 0..2 {
+        let (row, file) = ((kings[side] / 8) as i64, (kings[side] % 8) as i64);
+        let forward = forward(side);
+        for f in cmp::max(file - 1, 0)..=cmp::min(file + 1, 7) {
+            if pawns.at(side, row + forward, f) {
+                add(&mut term, side, w.shield_pawn, 1);
+            } else if pawns.at(side, row + 2 * forward, f) {
+                add(&mut term, side, w.shield_pawn_advanced, 1);
+            } else {
+                add(&mut term, side, w.shield_missing, 1);
+            }
+        }
+
+        let mut zone = [false; 64];
+        zone[kings[side]] = true;
+        for sq in board.attacks(kings[side]) {
+            zone[sq] = true;
+        }
+        let (mut attackers, mut weight) = (0, 0);
+        for sq in 0..64 {
+            let piece = board.get_piece(sq);
+            let kind = piece_type(piece);
+            if piece == 0
+                || (is_piece_white(piece) == (side == 0))
+                || kind == PieceType::Pawn as u32
+                || kind == PieceType::King as u32
+            {
+                continue;
+            }
+            let hits = board.attacks(sq).iter().filter(|&&sq| zone[sq]).count() as i64;
+            if hits > 0 {
+                attackers += 1;
+                weight += w.king_attack_weight[(kind - PieceType::Knight as u32) as usize] * hits;
+            }
+        }
+        term[side][0] -= weight * w.king_attackers_scale[cmp::min(attackers, 7)] / 100;
+    }
+    term
+}
+
+// Rooks on files with no pawns at all (open), or with only enemy pawns (semi-open), [middlegame,
+// endgame].
+const ROOK_OPEN_FILE: [i64; 2] = [40, 15];
+const ROOK_SEMI_OPEN_FILE: [i64; 2] = [20, 8];
+// A rook on the enemy's second rank (the seventh, from its own side), where it cuts off a king on
+// the back rank and eats the pawns that haven't moved. Only when there is a king to cut off or
+// pawns to eat.
+const ROOK_ON_SEVENTH: [i64; 2] = [20, 35];
+
+// Rook placement: rooks need open lines and the seventh rank, which the piece-square tables can't
+// see.
+fn rooks(board: &ArrayBoard, pawns: &Pawns) -> Term {
+    let w = weights();
+    let mut term = [[0; 2]; 2];
+    let kings = king_squares(board);
+    for sq in 0..64 {
+        let piece = board.get_piece(sq);
+        if piece_type(piece) != PieceType::Rook as u32 {
+            continue;
+        }
+        let side = !is_piece_white(piece) as usize;
+        let file = sq % 8;
+        if pawns.on_file(side, file) == 0 {
+            if pawns.on_file(1 - side, file) == 0 {
+                add(&mut term, side, w.rook_open_file, 1);
+            } else {
+                add(&mut term, side, w.rook_semi_open_file, 1);
+            }
+        }
+        let (seventh, back_rank) = if side == 0 { (1, 0) } else { (6, 7) };
+        if sq / 8 == seventh
+            && (kings[1 - side] / 8 == back_rank
+                || (0..8).any(|f| pawns.at(1 - side, seventh as i64, f)))
+        {
+            add(&mut term, side, w.rook_on_seventh, 1);
+        }
+    }
+    term
+}
+
+// Rough piece values for deciding whether an attacker is worth less than its target, by piece type
+// (pawn to king). Kings never count as the lesser attacker.
+const THREAT_CLASS: [i64; 7] = [0, 1, 3, 3, 5, 9, 100];
+// For attacking a knight, bishop, rook or queen with a lesser piece, [middlegame, endgame].
+const THREAT_BY_LESSER: [[i64; 2]; 4] = [[30, 25], [30, 25], [45, 40], [60, 50]];
+// For attacking a piece (not a pawn) that nothing defends.
+const HANGING: [i64; 2] = [30, 20];
+// For each piece (not a pawn) a pawn could attack by a safe push.
+const PAWN_PUSH_THREAT: [i64; 2] = [20, 15];
+
+// Who attacks each square: by side, the number of attackers, and the class of the cheapest.
+struct Attacks {
+    count: [[i64; 64]; 2],
+    least: [[i64; 64]; 2],
+}
+
+impl Attacks {
+    fn new(board: &ArrayBoard) -> Attacks {
+        let mut attacks = Attacks {
+            count: [[0; 64]; 2],
+            least: [[i64::MAX; 64]; 2],
+        };
+        for sq in 0..64 {
+            let piece = board.get_piece(sq);
+            if piece == 0 {
+                continue;
+            }
+            let side = !is_piece_white(piece) as usize;
+            let class = THREAT_CLASS[piece_type(piece) as usize];
+            for target in board.attacks(sq) {
+                attacks.count[side][target] += 1;
+                attacks.least[side][target] = cmp::min(attacks.least[side][target], class);
+            }
+        }
+        attacks
+    }
+}
+
+// Threats: enemy pieces attacked by something worth less than them, or attacked and left
+// undefended, and pawns one safe push away from attacking enemy pieces. They are credited to the
+// side making them.
+fn threats(board: &ArrayBoard, pawns: &Pawns) -> Term {
+    let w = weights();
+    let mut term = [[0; 2]; 2];
+    let attacks = Attacks::new(board);
+    for sq in 0..64 {
+        let piece = board.get_piece(sq);
+        let kind = piece_type(piece) as usize;
+        if piece == 0 || kind == PieceType::Pawn as usize || kind == PieceType::King as usize {
+            continue;
+        }
+        // The side that could take it.
+        let attacker = is_piece_white(piece) as usize;
+        if attacks.count[attacker][sq] == 0 {
+            continue;
+        }
+        if attacks.least[attacker][sq] < THREAT_CLASS[kind] {
+            add(
+                &mut term,
+                attacker,
+                w.threat_by_lesser[kind - PieceType::Knight as usize],
+                1,
+            );
+        } else if attacks.count[1 - attacker][sq] == 0 {
+            add(&mut term, attacker, w.hanging, 1);
+        }
+    }
+    for side in 0..2 {
+        let forward = forward(side);
+        for (sq, row, file) in pawns.squares(side) {
+            // Pushes to the last rank are promotions, not threats.
+            if !(1..7).contains(&(row + forward)) {
+                continue;
+            }
+            let push = (sq as i64 + 8 * forward) as usize;
+            let safe = attacks.count[1 - side][push] == 0 || attacks.count[side][push] > 0;
+            if board.get_piece(push) != 0 || !safe || attacks.least[1 - side][push] == 1 {
+                continue;
+            }
+            for f in [file - 1, file + 1] {
+                if !(0..8).contains(&f) {
+                    continue;
+                }
+                let target = board.get_piece((push as i64 + 8 * forward - file + f) as usize);
+                let kind = piece_type(target);
+                if target != 0
+                    && (is_piece_white(target) != (side == 0))
+                    && kind != PieceType::Pawn as u32
+                    && kind != PieceType::King as u32
+                {
+                    add(&mut term, side, w.pawn_push_threat, 1);
+                }
+            }
+        }
+    }
+    term
+}
+
+// Space, per safe square, scaled by how closed the position is: by one plus the number of files
+// where a pawn is blocked head-on by an enemy pawn, up to SPACE_MAX_SCALE. Space only matters while
+// there are pieces to use it, so it has no endgame weight.
+const SPACE: [i64; 2] = [1, 0];
+const SPACE_MAX_SCALE: i64 = 4;
+
+// Whether a pawn of `side` attacks the square at `row` and `file`.
+fn pawn_attacks(pawns: &Pawns, side: usize, row: i64, file: i64) -> bool {
+    let behind = row - forward(side);
+    pawns.at(side, behind, file - 1) || pawns.at(side, behind, file + 1)
+}
+
+// Space: safe squares in the centre files (c to f) from a side's second rank up to its fifth, just
+// into the opponent's half, that are behind its own pawns or guarded by them. Safe means no enemy
+// pawn attacks the square; squares holding its own pawns don't count.
+fn space(pawns: &Pawns) -> Term {
+    let w = weights();
+    let mut term = [[0; 2]; 2];
+    let mut locked = 0;
+    for (_, row, file) in pawns.squares(0) {
+        if pawns.at(1, row - 1, file) {
+            locked += 1;
+        }
+    }
+    let scale = cmp::min(1 + locked, SPACE_MAX_SCALE);
+    for side in 0..2 {
+        let forward = forward(side);
+        let home = if side == 0 { 7 } else { 0 };
+        let mut squares = 0;
+        for rank in 1..5 {
+            let row = home + rank * forward;
+            for file in 2..6 {
+                if pawns.at(side, row, file) || pawn_attacks(pawns, 1 - side, row, file) {
+                    continue;
+                }
+                let behind_pawn = (1..8).any(|ahead| pawns.at(side, row + ahead * forward, file));
+                if behind_pawn || pawn_attacks(pawns, side, row, file) {
+                    squares += 1;
+                }
+            }
+        }
+        add(&mut term, side, w.space, squares * scale);
+    }
+    term
+}
+
+// The side to move is a move ahead of a static evaluation, which scores positions as if neither
+// side were, so it gets a bonus. Without it, scores swing between iterations that end on either
+// side's move.
+const TEMPO: [i64; 2] = [15, 15];
+
+fn tempo(board: &ArrayBoard) -> Term {
+    let w = weights();
+    let mut term = [[0; 2]; 2];
+    add(&mut term, !board.white_to_move() as usize, w.tempo, 1);
+    term
+}
+
+// King tropism, for a knight, bishop, rook and queen, per square closer to the enemy king than the
+// far side of the board, [middlegame, endgame]. Queens and knights need to be close to attack;
+// the sliders less so.
+const TROPISM: [[i64; 2]; 4] = [[3, 1], [1, 0], [2, 1], [4, 2]];
+
+// King tropism: pieces near the enemy king, which may not attack its zone yet (king safety counts
+// those that do) but are in place to.
+fn tropism(board: &ArrayBoard) -> Term {
+    let w = weights();
+    let mut term = [[0; 2]; 2];
+    let kings = king_squares(board);
+    for sq in 0..64 {
+        let piece = board.get_piece(sq);
+        let kind = piece_type(piece);
+        if kind < PieceType::Knight as u32 || kind > PieceType::Queen as u32 {
+            continue;
+        }
+        let side = !is_piece_white(piece) as usize;
+        let king = kings[1 - side];
+        let distance = cmp::max((sq / 8).abs_diff(king / 8), (sq % 8).abs_diff(king % 8)) as i64;
+        add(
+            &mut term,
+            side,
+            w.tropism[(kind - PieceType::Knight as u32) as usize],
+            7 - distance,
+        );
+    }
+    term
+}
+
+// The pieces on the board, per side ([white, black]): by type, pawns to kings, and bishops by the
+// colour of their square, light then dark.
+struct Material {
+    pieces: [[i64; 6]; 2],
+    bishops: [[i64; 2]; 2],
+}
+
+impl Material {
+    fn new(board: &ArrayBoard) -> Material {
+        let mut material = Material {
+            pieces: [[0; 6]; 2],
+            bishops: [[0; 2]; 2],
+        };
+        for sq in 0..64 {
+            let piece = board.get_piece(sq);
+            if piece == 0 {
+                continue;
+            }
+            let side = !is_piece_white(piece) as usize;
+            let kind = piece_type(piece);
+            material.pieces[side][kind as usize - 1] += 1;
+            if kind == PieceType::Bishop as u32 {
+                material.bishops[side][(sq / 8 + sq % 8) % 2] += 1;
+            }
+        }
+        material
+    }
+
+    fn bare_king(&self, side: usize) -> bool {
+        self.pieces[side][..5] == [0; 5]
+    }
+
+    // Whether `side` has the material to force mate against a bare king: a pawn, a rook or queen,
+    // bishops on both colours, bishop and knight, or three knights. Two knights can only mate a
+    // king that walks into it.
+    fn can_mate(&self, side: usize) -> bool {
+        let [pawns, knights, bishops, rooks, queens, _] = self.pieces[side];
+        let [light_bishops, dark_bishops] = self.bishops[side];
+        pawns + rooks + queens > 0
+            || (light_bishops > 0 && dark_bishops > 0)
+            || (knights > 0 && bishops > 0)
+            || knights >= 3
+    }
+
+    // The middlegame value of `side`'s pieces other than pawns and the king.
+    fn non_pawn(&self, side: usize) -> i64 {
+        let w = weights();
+        (1..5)
+            .map(|kind| self.pieces[side][kind] * w.mg_piece_values[kind])
+            .sum()
+    }
+}
+
+// Material imbalance, [middlegame, endgame], after Kaufman: for the bishop pair; per knight and
+// per rook for each of the side's own pawns over five, as knights gain with more pawns on the
+// board and rooks want open lines; for a second rook and per rook alongside a queen, which overlap
+// in what they do; for two minors against a rook; and for a minor against three or more pawns.
+const BISHOP_PAIR: [i64; 2] = [30, 50];
+const KNIGHT_PAWNS: [i64; 2] = [6, 6];
+const ROOK_PAWNS: [i64; 2] = [-12, -12];
+const ROOK_REDUNDANCY: [i64; 2] = [-15, -10];
+const QUEEN_ROOK_REDUNDANCY: [i64; 2] = [-10, -5];
+const TWO_MINORS_VS_ROOK: [i64; 2] = [40, 10];
+const MINOR_VS_PAWNS: [i64; 2] = [0, -40];
+
+// Material imbalance: what pieces are worth beyond their values, depending on what else is on the
+// board, on either side.
+fn imbalance(material: &Material) -> Term {
+    let w = weights();
+    let mut term = [[0; 2]; 2];
+    for side in 0..2 {
+        let [pawns, knights, bishops, rooks, queens, _] = material.pieces[side];
+        let [their_pawns, their_knights, their_bishops, their_rooks, their_queens, _] =
+            material.pieces[1 - side];
+        if material.bishops[side].iter().all(|&count| count > 0) {
+            add(&mut term, side, w.bishop_pair, 1);
+        }
+        add(&mut term, side, w.knight_pawns, knights * (pawns - 5));
+        add(&mut term, side, w.rook_pawns, rooks * (pawns - 5));
+        if rooks >= 2 {
+            add(&mut term, side, w.rook_redundancy, 1);
+        }
+        if queens > 0 {
+            add(&mut term, side, w.queen_rook_redundancy, rooks);
+        }
+        let minors = knights + bishops - their_knights - their_bishops;
+        if minors == 2 && rooks - their_rooks == -1 && queens == their_queens {
+            add(&mut term, side, w.two_minors_vs_rook, 1);
+        }
+        if minors == 1 && rooks == their_rooks && queens == their_queens && their_pawns - pawns >= 3
+        {
+            add(&mut term, side, w.minor_vs_pawns, 1);
+        }
+    }
+    term
+}
+
+// Mop-up, against a bare king: per step the kings are closer than the far side of the board and per
+// step the lone king is from the centre, or with bishop and knight, per step it is closer to a
+// corner the bishop covers than the far side of the board.
+const MOP_UP_EDGE: [i64; 2] = [10, 10];
+const MOP_UP_KINGS: [i64; 2] = [4, 4];
+const MOP_UP_CORNER: [i64; 2] = [10, 10];
+
+// Mop-up: with mating material against a bare king, the mate takes driving the king to the edge
+// (to a corner the bishop covers, with bishop and knight) and bringing our own king up, which the
+// other terms have no idea of. Without this the position is just won material, and the search
+// shuffles around until the fifty-move rule unless the mate is within its depth.
+fn mop_up(board: &ArrayBoard, material: &Material) -> Term {
+    let w = weights();
+    let mut term = [[0; 2]; 2];
+    let Some(side) = (0..2).find(|&side| material.bare_king(1 - side)) else {
+        return term;
+    };
+    if !material.can_mate(side) {
+        return term;
+    }
+    let [_, knights, _, rooks, queens, _] = material.pieces[side];
+    let [light_bishops, dark_bishops] = material.bishops[side];
+    let majors = rooks + queens;
+    let kings = king_squares(board);
+    let (king, lone) = (kings[side], kings[1 - side]);
+    let kings_apart = (king / 8).abs_diff(lone / 8) + (king % 8).abs_diff(lone % 8);
+    add(&mut term, side, w.mop_up_kings, 14 - kings_apart as i64);
+    if majors == 0 && knights > 0 && (light_bishops > 0) != (dark_bishops > 0) {
+        // The mate is only possible in a corner the bishop covers, so any other one is no better
+        // than the centre. a8 and h1 are light squares, a1 and h8 dark.
+        let corners: [usize; 2] = if light_bishops > 0 { [0, 63] } else { [7, 56] };
+        let to_corner = corners
+            .iter()
+            .map(|&corner| (lone / 8).abs_diff(corner / 8) + (lone % 8).abs_diff(corner % 8))
+            .min()
+            .unwrap() as i64;
+        add(&mut term, side, w.mop_up_corner, 14 - to_corner);
+    } else {
+        let (row, file) = ((lone / 8) as i64, (lone % 8) as i64);
+        let from_centre = cmp::max(3 - row, row - 4) + cmp::max(3 - file, file - 4);
+        add(&mut term, side, w.mop_up_edge, from_centre);
+    }
+    term
+}
+
+// The terms on top of the material and piece-square tables, by name.
+fn positional_terms(board: &ArrayBoard) -> [(&'static str, Term); 11] {
+    let entry = pawn_entry(board);
+    let pawns = entry.pawns;
+    let material = Material::new(board);
+    [
+        ("Pawns", entry.structure),
+        ("Passed", passed_pawns(board, &pawns, entry.passers)),
+        (
+            "Unstoppable",
+            unstoppable_passers(board, &pawns, &material, entry.passers),
+        ),
+        ("King safety", king_safety(board, &pawns)),
+        ("Rooks", rooks(board, &pawns)),
+        ("Threats", threats(board, &pawns)),
+        ("Tropism", tropism(board)),
+        ("Space", space(&pawns)),
+        ("Tempo", tempo(board)),
+        ("Imbalance", imbalance(&material)),
+        ("Mop-up", mop_up(board, &material)),
+    ]
+}
+
+// Endgame scale factors, out of SCALE_NORMAL, for drawish endings: opposite-coloured bishops with
+// only pawns besides, and with other pieces; a rook ending a pawn up with at most three pawns,
+// with pawns on both wings and on one; and without pawns, less than a bishop up.
+const SCALE_NORMAL: i64 = 64;
+const DRAWISH_SCALE: [i64; 5] = [16, 44, 40, 24, 12];
+
+// How far the endgame score, which favours `strong`, is to be trusted: endings that are often not
+// won however much the material and the rest say are scaled towards a draw.
+fn scale_factor(board: &ArrayBoard, strong: usize) -> i64 {
+    let w = weights();
+    let material = Material::new(board);
+    let weak = 1 - strong;
+    let [pawns, knights, bishops, rooks, queens, _] = material.pieces[strong];
+    let [weak_pawns, weak_knights, weak_bishops, weak_rooks, weak_queens, _] =
+        material.pieces[weak];
+    if bishops == 1 && weak_bishops == 1 && material.bishops[strong] != material.bishops[weak] {
+        let others = knights + rooks + queens + weak_knights + weak_rooks + weak_queens;
+        return w.drawish_scale[if others == 0 { 0 } else { 1 }];
+    }
+    let minors = knights + bishops + weak_knights + weak_bishops;
+    if rooks == 1
+        && weak_rooks == 1
+        && minors + queens + weak_queens == 0
+        && pawns == weak_pawns + 1
+        && pawns <= 3
+    {
+        let files: Vec<usize> = (0..64)
+            .filter(|&sq| piece_type(board.get_piece(sq)) == PieceType::Pawn as u32)
+            .map(|sq| sq % 8)
+            .collect();
+        let one_wing = files.iter().all(|&file| file < 4) || files.iter().all(|&file| file >= 4);
+        return w.drawish_scale[if one_wing { 3 } else { 2 }];
+    }
+    let advantage = material.non_pawn(strong) - material.non_pawn(weak);
+    if pawns == 0 && advantage <= w.mg_piece_values[PieceType::Bishop as usize - 1] {
+        return w.drawish_scale[4];
+    }
+    SCALE_NORMAL
+}
+
+// The middlegame score's share of the evaluation at `game_phase`, out of TAPER_SCALE, from the
+// taper curve.
+fn middlegame_share(game_phase: i64) -> i64 {
+    let w = weights();
+    let buckets = w.taper_curve.len() as i64 - 1;
+    let position = game_phase.clamp(0, w.phase_total) * buckets;
+    let bucket = cmp::min(position / w.phase_total, buckets - 1);
+    let (low, high) = (
+        w.taper_curve[bucket as usize],
+        w.taper_curve[bucket as usize + 1],
+    );
+    low + (high - low) * (position - bucket * w.phase_total) / w.phase_total
+}
+
+// Interpolates between the middlegame and endgame scores by the game phase.
+fn taper(game_phase: i64, mg_score: i64, eg_score: i64) -> i64 {
+    let mg_share = middlegame_share(game_phase);
+    (mg_share * mg_score + (TAPER_SCALE - mg_share) * eg_score) / TAPER_SCALE
+}
+
+// Evaluation cache: the static evaluations of recently evaluated positions, by Zobrist key, as the
+// search evaluates the same positions over and over (transpositions in the quiescence search,
+// mostly). Per thread, like the pawn hash table. Key 0 marks an empty slot. The scores are from
+// before the halfmove clock scaling, as the key doesn't cover the clock.
+const EVAL_CACHE_SIZE: usize = 1 << 16;
+
+thread_local! {
+    static EVAL_CACHE: RefCell<Vec<(u64, i64)>> = RefCell::new(vec![(0, 0); EVAL_CACHE_SIZE]);
+}
+
+fn cache_eval(key: u64, score: i64) {
+    EVAL_CACHE
+        .with(|cache| cache.borrow_mut()[(key % EVAL_CACHE_SIZE as u64) as usize] = (key, score));
+}
+
+fn cached_eval(key: u64) -> Option<i64> {
+    EVAL_CACHE.with(|cache| {
+        let (cached_key, score) = cache.borrow()[(key % EVAL_CACHE_SIZE as u64) as usize];
+        (cached_key == key).then_some(score)
+    })
+}
+
+// A king and pawn against king that the KPK bitbase says is won is worth less than a queen, so
+// that promoting is always progress, and more the further on the pawn is. Drawn ones are 0.
+const KPK_WIN: i64 = 300;
+const KPK_WIN_PER_RANK: i64 = 20;
+
+fn kpk_score(board: &ArrayBoard) -> Option<i64> {
+    let win = kpk::probe(board)?;
+    if !win {
+        return Some(0);
+    }
+    let pawn = (0..64)
+        .find(|&sq| piece_type(board.get_piece(sq)) == PieceType::Pawn as u32)
+        .unwrap();
+    let white = is_piece_white(board.get_piece(pawn));
+    let rank = if white { 7 - pawn / 8 } else { pawn / 8 } as i64;
+    let score = KPK_WIN + KPK_WIN_PER_RANK * rank;
+    Some(if white == board.white_to_move() {
+        score
+    } else {
+        -score
+    })
+}
+
+// `score`, for the side to move, as nothing better than a draw for a side that can't mate a bare
+// king, such as one with a lone bishop or knight, whatever its pieces' squares say. Against pawns
+// it may still win, as they can leave the king no room (two knights mate that way), so then the
+// score stands.
+fn clamp_unwinnable(board: &ArrayBoard, score: i64) -> i64 {
+    let favoured = ((score > 0) != board.white_to_move()) as usize;
+    let material = Material::new(board);
+    if score == 0 || material.can_mate(favoured) || material.pieces[1 - favoured][0] > 0 {
+        return score;
+    }
+    0
+}
+
+// Evaluations shrink as the fifty-move counter climbs, to half of what they were when it reaches
+// fifty moves, so that the side that is better makes progress (a capture or pawn move resets the
+// counter) while there is time to, rather than shuffling around a won position until it is drawn.
+const HALFMOVE_SCALE: i64 = 200;
+
+fn scale_by_halfmove_clock(board: &ArrayBoard, score: i64) -> i64 {
+    let halfmoves = cmp::min(board.halfmove_clock(), 100) as i64;
+    score * (HALFMOVE_SCALE - halfmoves) / HALFMOVE_SCALE
+}
+
+// The static evaluation of `board`, from the side to move's point of view.
+pub fn eval(board: ArrayBoard) -> i64 {
+    let key = board.zobrist_key() ^ nnue::network_key();
+    let score = cached_eval(key).unwrap_or_else(|| {
+        let score = kpk_score(&board).unwrap_or_else(|| {
+            let score = nnue::evaluate(&board).unwrap_or_else(|| evaluate(&board));
+            clamp_unwinnable(&board, score)
+        });
+        cache_eval(key, score);
+        score
+    });
+    scale_by_halfmove_clock(&board, score)
+}
+
+// The network's evaluation of `board`, the search's current position, from the search's
+// accumulators, and cached like eval's.
+pub fn network_eval(board: ArrayBoard, accumulators: &mut nnue::Accumulators) -> i64 {
+    let key = board.zobrist_key() ^ nnue::network_key();
+    let score = cached_eval(key).unwrap_or_else(|| {
+        let score =
+            kpk_score(&board).unwrap_or_else(|| clamp_unwinnable(&board, accumulators.evaluate()));
+        cache_eval(key, score);
+        score
+    });
+    scale_by_halfmove_clock(&board, score)
+}
+
+// How far the positional terms are assumed to be able to move the material and piece-square
+// score, for lazy_eval.
+const LAZY_EVAL_MARGIN: i64 = 400;
+
+// The static evaluation, or just the material and piece-square score when that is so far outside
+// the window from `alpha` to `beta` that the rest of the evaluation couldn't bring it back in: all
+// the caller needs to know then is which side of the window it is on.
+pub fn lazy_eval(board: ArrayBoard, alpha: i64, beta: i64) -> i64 {
+    // The network's evaluation has no material score to go by.
+    if nnue::network_key() != 0 {
+        return eval(board);
+    }
+    if let Some(score) = cached_eval(board.zobrist_key()) {
+        return scale_by_halfmove_clock(&board, score);
+    }
+    let [[w_mg, w_eg], [b_mg, b_eg]] = board.psqt();
+    let score = taper(board.game_phase(), w_mg - b_mg, w_eg - b_eg);
+    let score = if board.white_to_move() { score } else { -score };
+    let score = scale_by_halfmove_clock(&board, score);
+    if score - LAZY_EVAL_MARGIN >= beta || score + LAZY_EVAL_MARGIN <= alpha {
+        return score;
+    }
+    eval(board)
+}
+
+fn evaluate(board: &ArrayBoard) -> i64 {
+    let [[mut w_mg, mut w_eg], [mut b_mg, mut b_eg]] = board.psqt();
+    for (_, term) in positional_terms(board) {
+        w_mg += term[0][0];
+        w_eg += term[0][1];
+        b_mg += term[1][0];
+        b_eg += term[1][1];
+    }
+    let (mg_score, eg_score) = if board.white_to_move() {
+        (w_mg - b_mg, w_eg - b_eg)
+    } else {
+        (b_mg - w_mg, b_eg - w_eg)
+    };
+    let scale = scale_factor(board, (w_eg < b_eg) as usize);
+    taper(
+        board.game_phase(),
+        mg_score,
+        eg_score * scale / SCALE_NORMAL,
+    )
+}
+
+// The static evaluation broken down by term, for the `eval` command and for tuning: every term,
+// material and piece-square tables first, from each side's own point of view.
+pub struct EvalTrace {
+    pub terms: Vec<(&'static str, Term)>,
+    pub game_phase: i64,
+    // The middlegame score's share of the evaluation at that phase, out of TAPER_SCALE.
+    pub middlegame_share: i64,
+    // What the endgame score is scaled by, out of SCALE_NORMAL.
+    pub scale: i64,
+}
+
+impl EvalTrace {
+    // The terms added up.
+    pub fn total(&self) -> Term {
+        let mut total = [[0; 2]; 2];
+        for (_, term) in &self.terms {
+            for side in 0..2 {
+                for stage in 0..2 {
+                    total[side][stage] += term[side][stage];
+                }
+            }
+        }
+        total
+    }
+}
+
+pub fn eval_trace(board: &ArrayBoard) -> EvalTrace {
+    let mut material = [[0; 2]; 2];
+    let mut pst = [[0; 2]; 2];
+    let w = weights();
+    for sq in 0..64 {
+        let piece = board.get_piece(sq) as usize;
+        if piece == 0 {
+            continue;
+        }
+        let piece_f = piece - 2;
+        let side = !is_piece_white(piece as u32) as usize;
+        let (mg_value, eg_value) = (
+            w.mg_piece_values[piece_f / 2],
+            w.eg_piece_values[piece_f / 2],
+        );
+        material[side][0] += mg_value;
+        material[side][1] += eg_value;
+        pst[side][0] += w.mg_table[piece_f][sq] as i64 - mg_value;
+        pst[side][1] += w.eg_table[piece_f][sq] as i64 - eg_value;
+    }
+    let mut terms = vec![("Material", material), ("PST", pst)];
+    terms.extend(positional_terms(board));
+    let mut trace = EvalTrace {
+        terms,
+        game_phase: board.game_phase(),
+        middlegame_share: middlegame_share(board.game_phase()),
+        scale: SCALE_NORMAL,
+    };
+    let total = trace.total();
+    trace.scale = scale_factor(board, (total[0][1] < total[1][1]) as usize);
+    trace
+}
+
+// Prints the static evaluation broken down by term, for the `eval` command. Terms are from each
+// side's own point of view; the final score is from white's.
+pub fn print_eval(board: ArrayBoard) {
+    let print_row = |name: &str, t: Term| {
+        send!(
+            "{name:>12} | {:>5} {:>5} | {:>5} {:>5} | {:>5} {:>5}",
+            t[0][0],
+            t[0][1],
+            t[1][0],
+            t[1][1],
+            t[0][0] - t[1][0],
+            t[0][1] - t[1][1]
+        )
+    };
+    let trace = eval_trace(&board);
+    send!("        Term |    White    |    Black    |    Total");
+    send!("             |   MG    EG  |   MG    EG  |   MG    EG");
+    send!(" ------------+-------------+-------------+------------");
+    for (name, term) in &trace.terms {
+        print_row(name, *term);
+    }
+    send!(" ------------+-------------+-------------+------------");
+    print_row("Total", trace.total());
+    send!();
+    send!(
+        "Game phase: {} / {} (middlegame {}%)",
+        trace.game_phase,
+        weights().phase_total,
+        trace.middlegame_share * 100 / TAPER_SCALE
+    );
+    if trace.scale != SCALE_NORMAL {
+        send!("Endgame scale: {} / {SCALE_NORMAL}", trace.scale);
+    }
+    if board.halfmove_clock() > 0 {
+        send!(
+            "Halfmove clock: {} (scaled by {}%)",
+            board.halfmove_clock(),
+            scale_by_halfmove_clock(&board, 100)
+        );
+    }
+    if let Some(win) = kpk::probe(&board) {
+        send!("KPK bitbase: {}", if win { "win" } else { "draw" });
+    }
+    let white_view = |score: i64| if board.white_to_move() { score } else { -score };
+    if let Some(score) = nnue::evaluate(&board) {
+        send!(
+            "Classical evaluation: {} cp (white side)",
+            white_view(evaluate(&board))
+        );
+        send!("NNUE evaluation: {} cp (white side)", white_view(score));
+    }
+    send!(
+        "Final evaluation: {} cp (white side)",
+        white_view(eval(board))
+    );
+}
+
+// Checks that the evaluation is colorblind on each of `fens`: that the mirror of a position, with
+// the colors swapped, scores the same for its side to move, and that each of its terms is the
+// other side's term in the position. New terms that get a direction or a side wrong show up here.
+// Prints the positions that fail and how many did.
+pub fn symmetry_test(fens: &[&str]) {
+    let (mut checked, mut failures) = (0, 0);
+    for fen in fens {
+        let board = match ArrayBoard::parse_fen(fen) {
+            Ok(board) => board,
+            Err(e) => {
+                send!("Skipping invalid fen {fen}: {e}");
+                continue;
+            }
+        };
+        checked += 1;
+        let mirror = board.mirrored();
+        let (trace, mirror_trace) = (eval_trace(&board), eval_trace(&mirror));
+        let mut asymmetric: Vec<&str> = trace
+            .terms
+            .iter()
+            .zip(&mirror_trace.terms)
+            .filter(|((_, term), (_, mirror_term))| {
+                term[0] != mirror_term[1] || term[1] != mirror_term[0]
+            })
+            .map(|((name, _), _)| *name)
+            .collect();
+        let (score, mirror_score) = (eval(board), eval(mirror));
+        if score != mirror_score {
+            asymmetric.push("Final evaluation");
+        }
+        if !asymmetric.is_empty() {
+            failures += 1;
+            send!("Asymmetric: {fen}");
+            send!(
+                "  mirror {} scores {score} and {mirror_score}; terms: {}",
+                mirror.fen(),
+                asymmetric.join(", ")
+            );
+        }
+    }
+    send!("{failures} of {checked} positions evaluated asymmetrically");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every weight, in the format parse reads.
+    fn to_text(weights: &mut Weights) -> String {
+        weights
+            .by_name()
+            .iter()
+            .map(|(name, values)| format!("{name} = {values:?}\n"))
+            .collect()
+    }
+
+    #[test]
+    fn weights_round_trip() {
+        let mut changed = Weights::default();
+        for (_, values) in changed.by_name() {
+            values.iter_mut().for_each(|value| *value += 1);
+        }
+        changed.combine_tables();
+        let text = to_text(&mut changed);
+        let mut parsed = Weights::default();
+        parsed.parse(&text).unwrap();
+        assert_eq!(to_text(&mut parsed), text);
+        assert_eq!(parsed.mg_table, changed.mg_table);
+        assert_eq!(parsed.phase_total, changed.phase_total);
+    }
+
+    #[test]
+    fn weights_read_from_json_and_comments() {
+        let mut weights = Weights::default();
+        weights
+            .parse("{\"tempo\": [10, 5], \"bishop_pair\": [30, 50]}")
+            .unwrap();
+        assert_eq!((weights.tempo, weights.bishop_pair), ([10, 5], [30, 50]));
+        let text = "# tuned\n\
+                    tempo = [1, 2] # per side\n\
+                    passed_pawn = [[0, 1, 2, 3, 4, 5, 6, 7],\n  [7, 6, 5, 4, 3, 2, 1, 0]]\n";
+        weights.parse(text).unwrap();
+        assert_eq!(weights.tempo, [1, 2]);
+        assert_eq!(
+            weights.passed_pawn,
+            [[0, 1, 2, 3, 4, 5, 6, 7], [7, 6, 5, 4, 3, 2, 1, 0]]
+        );
+    }
+
+    #[test]
+    fn scores_without_mating_material_are_clamped() {
+        let clamped = |fen: &str, score| {
+            let board = ArrayBoard::parse_fen(fen).unwrap();
+            clamp_unwinnable(&board, score)
+        };
+        // Two knights can't mate a bare king, nor can a bishop with the other side to move.
+        assert_eq!(clamped("4k3/8/8/8/8/8/8/2NNK3 w - - 0 1", 400), 0);
+        assert_eq!(clamped("4k3/8/8/8/8/8/8/2B1K3 b - - 0 1", -300), 0);
+        // But they can win against pawns.
+        assert_eq!(clamped("4k3/4p3/8/8/8/8/8/2NNK3 w - - 0 1", 400), 400);
+        assert_eq!(clamped("4k3/4p3/8/8/8/8/8/2B1K3 b - - 0 1", -300), -300);
+        // A side that can mate keeps its score.
+        assert_eq!(clamped("4k3/8/8/8/8/8/8/3RK3 w - - 0 1", 500), 500);
+        assert_eq!(clamped("4k3/4p3/8/8/8/8/8/2NNK3 w - - 0 1", -50), -50);
+    }
+
+    #[test]
+    fn bad_weights_set_nothing() {
+        let mut text = String::from("tempo = [1, 2]\n");
+        text.push_str(&to_text(&mut Weights::default()));
+        // Cut off partway through a list, and cut off after a list's last value.
+        let truncated = &text[..text.len() - 8];
+        let unclosed = text.trim_end().trim_end_matches(']');
+        for bad in [
+            truncated,
+            unclosed,
+            "tempo = [1, 2]\nbishop_pair = [30 50]\nspace = [1, x]",
+            "tempo = [1, 2]\nbishop_pair [30, 50]",
+            "tempo = [1, 2]\nbishop_pairs = [30, 50]",
+            "tempo = [1, 2]\nbishop_pair = [30, 50, 70]",
+        ] {
+            let mut weights = Weights::default();
+            assert!(weights.parse(bad).is_err(), "{bad}");
+            assert_eq!(weights.tempo, TEMPO, "{bad}");
+        }
+    }
+}
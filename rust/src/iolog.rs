@@ -0,0 +1,66 @@
+// Optional log of the conversation with the GUI (the `Log File` option). When a GUI reports that
+// the engine disconnected or misbehaved, this log is usually the only record of what was said.
+use std::fs::File;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+// Prints a line to the GUI, recording it in the log file if one is open.
+macro_rules! send {
+    () => {
+        $crate::iolog::send_line("")
+    };
+    ($($arg:tt)*) => {
+        $crate::iolog::send_line(&format!($($arg)*))
+    };
+}
+
+// Starts logging to `path`, appending if it exists. An empty path (or "<empty>", which some GUIs
+// send for an empty string option) turns logging off.
+pub fn set_log_file(path: &str) {
+    let file = match path.trim() {
+        "" | "<empty>" => None,
+        path => match File::options().create(true).append(true).open(path) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                send_line(&format!("info string cannot open log file {path}: {e}"));
+                None
+            }
+        },
+    };
+    *LOG_FILE.lock().unwrap() = file;
+}
+
+// UTC time of day as hh:mm:ss.mmm.
+fn timestamp() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let secs = millis / 1000;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        millis % 1000
+    )
+}
+
+fn log(direction: &str, line: &str) {
+    if let Some(file) = LOG_FILE.lock().unwrap().as_mut() {
+        writeln!(file, "[{}] {direction} {}", timestamp(), line.trim_end()).ok();
+    }
+}
+
+// Records a line received from the GUI.
+pub fn log_input(line: &str) {
+    log("<<", line);
+}
+
+pub fn send_line(line: &str) {
+    println!("{line}");
+    log(">>", line);
+}
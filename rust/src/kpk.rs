@@ -0,0 +1,259 @@
+// KPK bitbase: whether king and pawn against king is a win, for every position, so that the
+// engine plays the ending perfectly instead of by the evaluation's rules of thumb. It is worked out
+// the first time it is needed, backwards from the positions that are decided on sight: the pawn
+// promotes safely, or is taken, or the lone king is stalemated. Whatever is still undecided when
+// nothing more can be decided is a draw. Working it out takes a fifth of a second or so.
+//
+// Positions are seen with the pawn as White's, going up the board, and (as the mirror image of a
+// position has the same result) on the a to d files.
+use super::arrayboard::{is_piece_white, piece_type, ArrayBoard, PieceType};
+use std::sync::OnceLock;
+
+// Side to move x white king x black king x pawn squares (files a to d, ranks 2 to 7).
+const SIZE: usize = 2 * 64 * 64 * 24;
+
+#[derive(Copy, Clone, PartialEq)]
+enum Outcome {
+    Invalid,
+    Unknown,
+    Draw,
+    Win,
+}
+
+// One bit per position, set for the wins.
+static BITBASE: OnceLock<Vec<u64>> = OnceLock::new();
+
+fn index(white_to_move: bool, white_king: usize, black_king: usize, pawn: usize) -> usize {
+    let pawn = ((pawn >> 3) - 1) * 4 + (pawn & 7);
+    ((white_to_move as usize * 64 + white_king) * 64 + black_king) * 24 + pawn
+}
+
+fn distance(a: usize, b: usize) -> usize {
+    let rows = (a >> 3).abs_diff(b >> 3);
+    let files = (a & 7).abs_diff(b & 7);
+    rows.max(files)
+}
+
+fn king_moves(sq: usize) -> impl Iterator<Item = usize> {
+    (0..64).filter(move |&to| distance(sq, to) == 1)
+}
+
+fn pawn_attacks(pawn: usize, sq: usize) -> bool {
+    (sq >> 3) + 1 == pawn >> 3 && (sq & 7).abs_diff(pawn & 7) == 1
+}
+
+// The result of a position as far as can be told without looking further ahead.
+fn classify(white_to_move: bool, white_king: usize, black_king: usize, pawn: usize) -> Outcome {
+    if white_king == black_king
+        || white_king == pawn
+        || black_king == pawn
+        || distance(white_king, black_king) <= 1
+        || (white_to_move && pawn_attacks(pawn, black_king))
+    {
+        return Outcome::Invalid;
+    }
+    if white_to_move {
+        // A pawn that promotes where the new queen can't be taken wins.
+        let queen = pawn - 8;
+        if pawn >> 3 == 1
+            && queen != white_king
+            && queen != black_king
+            && (distance(black_king, queen) > 1 || distance(white_king, queen) == 1)
+        {
+            return Outcome::Win;
+        }
+        return Outcome::Unknown;
+    }
+    let mut escapes = king_moves(black_king)
+        .filter(|&to| distance(to, white_king) > 1 && !pawn_attacks(pawn, to))
+        .peekable();
+    if escapes.peek().is_none() {
+        return match pawn_attacks(pawn, black_king) {
+            true => Outcome::Win,
+            false => Outcome::Draw,
+        };
+    }
+    if escapes.any(|to| to == pawn) {
+        return Outcome::Draw;
+    }
+    Outcome::Unknown
+}
+
+// The result of an undecided position from the results of the positions it leads to: a win if
+// any move wins (White to move) or every move does (Black to move), and likewise for draws.
+fn resolve(
+    results: &[Outcome],
+    white_to_move: bool,
+    white_king: usize,
+    black_king: usize,
+    pawn: usize,
+) -> Outcome {
+    let mut children = Vec::with_capacity(10);
+    if white_to_move {
+        for to in king_moves(white_king) {
+            if to != pawn && distance(to, black_king) > 1 {
+                children.push(index(false, to, black_king, pawn));
+            }
+        }
+        // Promotions were decided by classify.
+        let push = pawn - 8;
+        if pawn >> 3 > 1 && push != white_king && push != black_king {
+            children.push(index(false, white_king, black_king, push));
+            let double = push - 8;
+            if pawn >> 3 == 6 && double != white_king && double != black_king {
+                children.push(index(false, white_king, black_king, double));
+            }
+        }
+    } else {
+        for to in king_moves(black_king) {
+            if distance(to, white_king) > 1 && !pawn_attacks(pawn, to) {
+                children.push(index(true, white_king, to, pawn));
+            }
+        }
+    }
+    let (good, bad) = match white_to_move {
+        true => (Outcome::Win, Outcome::Draw),
+        false => (Outcome::Draw, Outcome::Win),
+    };
+    if children.iter().any(|&child| results[child] == good) {
+        good
+    } else if children.iter().all(|&child| results[child] == bad) {
+        bad
+    } else {
+        Outcome::Unknown
+    }
+}
+
+fn generate() -> Vec<u64> {
+    let mut results = vec![Outcome::Invalid; SIZE];
+    let mut positions = Vec::with_capacity(SIZE);
+    for white_to_move in [false, true] {
+        for white_king in 0..64 {
+            for black_king in 0..64 {
+                for pawn in (8..56).filter(|sq| sq & 7 < 4) {
+                    let position = (white_to_move, white_king, black_king, pawn);
+                    let result = classify(white_to_move, white_king, black_king, pawn);
+                    results[index(white_to_move, white_king, black_king, pawn)] = result;
+                    if result == Outcome::Unknown {
+                        positions.push(position);
+                    }
+                }
+            }
+        }
+    }
+    loop {
+        let mut changed = false;
+        for &(white_to_move, white_king, black_king, pawn) in &positions {
+            let i = index(white_to_move, white_king, black_king, pawn);
+            if results[i] != Outcome::Unknown {
+                continue;
+            }
+            let result = resolve(&results, white_to_move, white_king, black_king, pawn);
+            if result != Outcome::Unknown {
+                results[i] = result;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    let mut bits = vec![0u64; SIZE / 64];
+    for (i, result) in results.iter().enumerate() {
+        if *result == Outcome::Win {
+            bits[i / 64] |= 1 << (i % 64);
+        }
+    }
+    bits
+}
+
+// Whether the side with the pawn wins, if `board` is king and pawn against king.
+pub fn probe(board: &ArrayBoard) -> Option<bool> {
+    if board.game_phase() != 0 {
+        return None;
+    }
+    let (mut kings, mut pawn) = ([0; 2], None);
+    for sq in 0..64 {
+        let piece = board.get_piece(sq);
+        if piece == 0 {
+            continue;
+        }
+        let side = !is_piece_white(piece) as usize;
+        match piece_type(piece) {
+            t if t == PieceType::King as u32 => kings[side] = sq,
+            t if t == PieceType::Pawn as u32 && pawn.is_none() => pawn = Some((sq, side)),
+            _ => return None,
+        }
+    }
+    let (pawn, side) = pawn?;
+    // Seen from the pawn's side, as White, with the pawn on the a to d files.
+    let flip_rows = if side == 0 { 0 } else { 0b111000 };
+    let flip_files = if pawn & 7 < 4 { 0 } else { 0b111 };
+    let orient = |sq: usize| sq ^ flip_rows ^ flip_files;
+    let strong_to_move = board.white_to_move() == (side == 0);
+    let i = index(
+        strong_to_move,
+        orient(kings[side]),
+        orient(kings[1 - side]),
+        orient(pawn),
+    );
+    let bits = BITBASE.get_or_init(generate);
+    Some(bits[i / 64] & 1 << (i % 64) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe_fen(fen: &str) -> Option<bool> {
+        probe(&ArrayBoard::parse_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn rook_pawn_draws_with_the_king_in_the_corner() {
+        assert_eq!(probe_fen("7k/8/6K1/7P/8/8/8/8 w - - 0 1"), Some(false));
+        assert_eq!(probe_fen("k7/8/8/8/P7/8/8/K7 b - - 0 1"), Some(false));
+    }
+
+    #[test]
+    fn king_on_a_key_square_wins() {
+        // d6, e6 and f6 are the key squares of a pawn on e4: either side to move, it promotes.
+        for fen in [
+            "4k3/8/4K3/8/4P3/8/8/8 w - - 0 1",
+            "4k3/8/4K3/8/4P3/8/8/8 b - - 0 1",
+            "3k4/8/5K2/8/4P3/8/8/8 b - - 0 1",
+        ] {
+            assert_eq!(probe_fen(fen), Some(true), "{fen}");
+        }
+    }
+
+    #[test]
+    fn king_in_front_holds_by_stalemate() {
+        // Black, to move, has none.
+        assert_eq!(probe_fen("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), Some(false));
+        // Pushing e7+ only stalemates after Kd6 Kd8 or Kf6 Kf8.
+        assert_eq!(probe_fen("4k3/8/4P3/4K3/8/8/8/8 w - - 0 1"), Some(false));
+    }
+
+    #[test]
+    fn black_pawns_probe_like_white_ones() {
+        for fen in [
+            "7k/8/6K1/7P/8/8/8/8 w - - 0 1",
+            "4k3/8/4K3/8/4P3/8/8/8 b - - 0 1",
+            "4k3/8/4P3/4K3/8/8/8/8 w - - 0 1",
+            "8/8/1k6/8/8/8/1P6/1K6 w - - 0 1",
+            "5k2/8/8/8/8/2K5/5P2/8 b - - 0 1",
+        ] {
+            let board = ArrayBoard::parse_fen(fen).unwrap();
+            assert!(probe(&board).is_some(), "{fen}");
+            assert_eq!(probe(&board.mirrored()), probe(&board), "{fen}");
+        }
+        assert_eq!(probe_fen("8/8/8/8/4p3/4k3/8/4K3 b - - 0 1"), Some(true));
+    }
+
+    #[test]
+    fn other_endings_are_not_probed() {
+        assert_eq!(probe_fen("4k3/8/8/8/4P3/8/8/3RK3 w - - 0 1"), None);
+        assert_eq!(probe_fen("4k3/8/8/8/3PP3/8/8/4K3 w - - 0 1"), None);
+    }
+}
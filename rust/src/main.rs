@@ -1,73 +1,42 @@
 #![feature(test)]
+#![feature(portable_simd)]
 #![allow(unused_imports)]
 extern crate num;
 extern crate test;
 #[macro_use]
 extern crate num_derive;
 
+#[macro_use]
+mod iolog;
 mod arrayboard;
+mod bench;
 mod engine;
+mod eval;
+mod kpk;
+mod nnue;
+mod params;
+mod perft;
+mod play;
+mod timeman;
+mod trace;
+mod tt;
 mod uci;
+mod wdl;
+mod xboard;
 
 use arrayboard::ArrayBoard;
 use arrayboard::BitMove;
-use std::time::Instant;
+use perft::perft;
+use std::env;
+use std::io;
 use test::Bencher;
 
 const DO_PERFT: bool = false;
 
-fn perft(board: ArrayBoard, max_depth: u32, depth: u32) -> (u32, u32, u32, u32, u32) {
-    let (mut nodes, mut captures, mut castles, mut checks, mut promos) = (0, 0, 0, 0, 0);
-    if depth == (max_depth - 1) {
-        for mv in board.generate_moves() {
-            nodes += 1;
-            if mv.meta & arrayboard::generate_moves::MOVE_CAPTURE > 0 {
-                captures += 1;
-            }
-            if mv.meta & arrayboard::generate_moves::MOVE_CASTLE > 0 {
-                castles += 1;
-            }
-            if mv.meta & arrayboard::generate_moves::MOVE_CHECK > 0 {
-                checks += 1;
-            }
-            if mv.meta & arrayboard::generate_moves::MOVE_PROMO > 0 {
-                promos += 1;
-            }
-        }
-        return (nodes, captures, castles, checks, promos);
-    }
-    for mv in board.generate_moves() {
-        let new_board = board.make_move(&mv);
-        // println!("{}", &mv.to_string());
-        // new_board.pretty_print(true);
-        let (n, c1, c2, c3, p) = perft(new_board, max_depth, depth + 1);
-        nodes += n;
-        captures += c1;
-        castles += c2;
-        checks += c3;
-        promos += p;
-    }
-    (nodes, captures, castles, checks, promos)
-}
-
 fn main() {
     if DO_PERFT {
         let board = ArrayBoard::create_from_fen(arrayboard::PERFT2_FEN);
-        let start = Instant::now();
-        let depth = 5;
-        let (nodes, captures, castles, checks, promos) = perft(board, depth, 0);
-        let tm = start.elapsed().as_secs();
-        println!(
-            "Perft({depth}) results: \n    \
-             nodes: {nodes}\n    \
-             captures: {captures}\n    \
-             castles: {castles}\n    \
-             checks: {checks}\n    \
-             promos: {promos}\n    \
-             {:?}s,  {} nps",
-            tm,
-            nodes as u64 / tm
-        );
+        perft::print_perft(board, /* depth= */ 5);
         // let mut board = ArrayBoard::create_from_fen(arrayboard::STARTING_FEN);
         // board = board.make_move(&BitMove::from_string("a2a8"));
         // board.print_legal_moves();
@@ -76,8 +45,83 @@ fn main() {
         println!("=============================================================");
         println!("====           W A L R U S       B O T                   ====");
         println!("=============================================================");
-        uci::run();
+        let mut args: Vec<String> = env::args().skip(1).collect();
+        // `--weights <file>` replaces the evaluation's weights with tuned ones.
+        if let Some(i) = args.iter().position(|arg| arg == "--weights") {
+            let Some(path) = args.get(i + 1) else {
+                println!("usage: walrus_bot --weights <file>");
+                return;
+            };
+            if let Err(e) = eval::load_weights(path) {
+                println!("cannot load weights: {e}");
+                return;
+            }
+            args.drain(i..i + 2);
+        }
+        if args.first().map(String::as_str) == Some("play") {
+            let args: Vec<&str> = args[1..].iter().map(String::as_str).collect();
+            play::run(&args);
+            return;
+        }
+        if args.first().map(String::as_str) == Some("selfplay") {
+            let args: Vec<&str> = args[1..].iter().map(String::as_str).collect();
+            play::selfplay(&args);
+            return;
+        }
+        if args.first().map(String::as_str) == Some("mate") {
+            find_mate(&args[1..]);
+            return;
+        }
+        // The first command tells us which protocol the GUI speaks.
+        let mut first_line = String::new();
+        while first_line.trim().is_empty() {
+            first_line.clear();
+            match io::stdin().read_line(&mut first_line) {
+                Ok(0) | Err(_) => return,
+                Ok(_) => (),
+            }
+        }
+        let tokens: Vec<&str> = first_line.split_whitespace().collect();
+        if tokens[0] == "xboard" {
+            xboard::run();
+        } else if tokens[0] == "play" {
+            play::run(&tokens[1..]);
+        } else {
+            uci::run(first_line);
+        }
+    }
+}
+
+// `walrus_bot mate <moves> [fen]`: prints the shortest forced mate in at most that many moves from
+// the position (the starting position by default), if there is one.
+fn find_mate(args: &[String]) {
+    let Some(moves) = args.first().and_then(|n| n.parse::<u8>().ok()) else {
+        println!("usage: walrus_bot mate <moves> [fen]");
+        return;
+    };
+    let fen = match args.len() {
+        1 => arrayboard::STARTING_FEN.to_string(),
+        _ => args[1..].join(" "),
+    };
+    let mut board = match ArrayBoard::parse_fen(&fen) {
+        Ok(board) => board,
+        Err(err) => {
+            println!("invalid fen: {err}");
+            return;
+        }
+    };
+    let limits = engine::SearchLimits::default();
+    let result = engine::search_mate(board, moves, &limits, &engine::STOP);
+    if result.pv.is_empty() {
+        println!("No mate in {moves}.");
+        return;
+    }
+    let mut line = Vec::new();
+    for mv in &result.pv {
+        line.push(board.san(mv));
+        board = board.make_move(mv);
     }
+    println!("Mate in {}: {}", result.depth.div_ceil(2), line.join(" "));
 }
 
 #[bench]
@@ -0,0 +1,441 @@
+// NNUE evaluation: an efficiently updatable neural network that replaces the hand-crafted
+// evaluation when a network file is loaded (the `EvalFile` option).
+//
+// The network is HalfKP: each side's perspective has a feature for every piece other than the
+// kings, by its type, whose it is and its square, and by the square of the perspective's own king.
+// The features that are on are summed into an accumulator of `hidden` values per perspective,
+// which are clipped to [0, QA] and fed, side to move first, to a single output neuron.
+//
+// Network files are little-endian:
+//
+//   magic            8 bytes, "WALRUSNN"
+//   hidden           u32, the accumulator size
+//   feature biases   hidden x i16
+//   feature weights  FEATURES x hidden x i16, by feature
+//   output weights   2 x hidden x i16, side to move's half first
+//   output bias      i32
+use super::arrayboard::{is_piece_white, piece_type, ArrayBoard, PieceType};
+use std::fs;
+use std::simd::cmp::SimdOrd;
+use std::simd::num::SimdInt;
+use std::simd::Simd;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+const MAGIC: &[u8; 8] = b"WALRUSNN";
+// King squares x piece kinds (pawn to queen, own or enemy) x squares.
+const FEATURES: usize = 64 * 10 * 64;
+const MAX_HIDDEN: usize = 4096;
+// Accumulators are added to and read a vector of LANES values at a time, so the accumulator size
+// must be a multiple of it.
+const LANES: usize = 16;
+// Quantization: accumulator values are clipped to QA and output weights are scaled by QB, so the
+// output neuron's sum times SCALE / (QA * QB) is in centipawns.
+const QA: i32 = 255;
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+
+pub struct Network {
+    hidden: usize,
+    feature_biases: Vec<i16>,
+    feature_weights: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+static NETWORK: RwLock<Option<Arc<Network>>> = RwLock::new(None);
+// A hash of the loaded network file, or 0 without one, so that caches of evaluations made with
+// one network aren't used with another.
+static NETWORK_KEY: AtomicU64 = AtomicU64::new(0);
+
+// Reads the little-endian numbers of a network file in order.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        if self.bytes.len() < n {
+            return Err(String::from("file is truncated"));
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i16s(&mut self, n: usize) -> Result<Vec<i16>, String> {
+        Ok(self
+            .take(n * 2)?
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect())
+    }
+}
+
+impl Network {
+    fn parse(bytes: &[u8]) -> Result<Network, String> {
+        let mut reader = Reader { bytes };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(String::from("not a walrus-bot network"));
+        }
+        let hidden = reader.u32()? as usize;
+        if hidden == 0 || hidden > MAX_HIDDEN || !hidden.is_multiple_of(LANES) {
+            return Err(format!("bad accumulator size {hidden}"));
+        }
+        let network = Network {
+            hidden,
+            feature_biases: reader.i16s(hidden)?,
+            feature_weights: reader.i16s(FEATURES * hidden)?,
+            output_weights: reader.i16s(2 * hidden)?,
+            output_bias: reader.i32()?,
+        };
+        if !reader.bytes.is_empty() {
+            return Err(String::from("file is longer than its network"));
+        }
+        Ok(network)
+    }
+}
+
+// Loads the network in `path`, replacing the one in use. An empty path (or "<empty>") goes back
+// to the hand-crafted evaluation.
+pub fn set_network_file(path: &str) {
+    let network = match path.trim() {
+        "" | "<empty>" => None,
+        path => match fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| Ok((Network::parse(&bytes)?, bytes)))
+        {
+            Ok((network, bytes)) => {
+                send!(
+                    "info string loaded network {path} ({} hidden)",
+                    network.hidden
+                );
+                // FNV-1a, made nonzero.
+                let hash = bytes.iter().fold(0xcbf29ce484222325u64, |hash, &byte| {
+                    (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+                });
+                NETWORK_KEY.store(hash | 1, Ordering::Relaxed);
+                Some(Arc::new(network))
+            }
+            Err(e) => {
+                send!("info string cannot load network {path}: {e}");
+                None
+            }
+        },
+    };
+    if network.is_none() {
+        NETWORK_KEY.store(0, Ordering::Relaxed);
+    }
+    *NETWORK.write().unwrap() = network;
+}
+
+pub fn network_key() -> u64 {
+    NETWORK_KEY.load(Ordering::Relaxed)
+}
+
+fn network() -> Option<Arc<Network>> {
+    NETWORK.read().unwrap().clone()
+}
+
+// The feature of `piece` on `sq` for the perspective of `side` ([white, black]) with its king on
+// `king`. Squares are seen from the perspective's side, with its first rank first.
+fn feature(side: usize, king: usize, piece: u32, sq: usize) -> usize {
+    let orient = |sq: usize| if side == 0 { sq ^ 0b111000 } else { sq };
+    let own = (!is_piece_white(piece) as usize == side) as usize;
+    let kind = (piece_type(piece) - PieceType::Pawn as u32) as usize * 2 + 1 - own;
+    (orient(king) * 10 + kind) * 64 + orient(sq)
+}
+
+fn add_weights(accumulator: &mut [i16], weights: &[i16]) {
+    for (values, weights) in accumulator
+        .chunks_exact_mut(LANES)
+        .zip(weights.chunks_exact(LANES))
+    {
+        (Simd::<i16, LANES>::from_slice(values) + Simd::from_slice(weights)).copy_to_slice(values);
+    }
+}
+
+fn subtract_weights(accumulator: &mut [i16], weights: &[i16]) {
+    for (values, weights) in accumulator
+        .chunks_exact_mut(LANES)
+        .zip(weights.chunks_exact(LANES))
+    {
+        (Simd::<i16, LANES>::from_slice(values) - Simd::from_slice(weights)).copy_to_slice(values);
+    }
+}
+
+fn king_squares(board: &ArrayBoard) -> [usize; 2] {
+    let mut kings = [0; 2];
+    for sq in 0..64 {
+        let piece = board.get_piece(sq);
+        if piece_type(piece) == PieceType::King as u32 {
+            kings[!is_piece_white(piece) as usize] = sq;
+        }
+    }
+    kings
+}
+
+impl Network {
+    fn weights(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden..][..self.hidden]
+    }
+
+    // Adds up the accumulator of `side`'s perspective of `board` from scratch.
+    fn refresh(&self, board: &ArrayBoard, side: usize, accumulator: &mut [i16]) {
+        let king = king_squares(board)[side];
+        accumulator.copy_from_slice(&self.feature_biases);
+        for sq in 0..64 {
+            let piece = board.get_piece(sq);
+            if piece != 0 && piece_type(piece) != PieceType::King as u32 {
+                add_weights(accumulator, self.weights(feature(side, king, piece, sq)));
+            }
+        }
+    }
+
+    // The output for accumulators [side to move, other side], in centipawns.
+    fn output(&self, us: &[i16], them: &[i16]) -> i64 {
+        let (our_weights, their_weights) = self.output_weights.split_at(self.hidden);
+        let (zero, qa) = (Simd::splat(0), Simd::splat(QA as i16));
+        let mut sum = self.output_bias as i64;
+        for (accumulator, weights) in [(us, our_weights), (them, their_weights)] {
+            for (values, weights) in accumulator
+                .chunks_exact(LANES)
+                .zip(weights.chunks_exact(LANES))
+            {
+                let clipped = Simd::<i16, LANES>::from_slice(values).simd_clamp(zero, qa);
+                let products =
+                    clipped.cast::<i32>() * Simd::<i16, LANES>::from_slice(weights).cast::<i32>();
+                sum += products.reduce_sum() as i64;
+            }
+        }
+        sum * SCALE as i64 / (QA * QB) as i64
+    }
+}
+
+// The network's evaluation of `board` from the side to move's point of view, if one is loaded,
+// with its accumulators added up from scratch.
+pub fn evaluate(board: &ArrayBoard) -> Option<i64> {
+    let network = network()?;
+    let mut accumulators = [vec![0; network.hidden], vec![0; network.hidden]];
+    for (side, accumulator) in accumulators.iter_mut().enumerate() {
+        network.refresh(board, side, accumulator);
+    }
+    Some(output(&network, board, &accumulators))
+}
+
+fn output(network: &Network, board: &ArrayBoard, [white, black]: &[Vec<i16>; 2]) -> i64 {
+    if board.white_to_move() {
+        network.output(white, black)
+    } else {
+        network.output(black, white)
+    }
+}
+
+// The accumulators of the positions along the line being searched, one entry per ply from the
+// root, so that each position's are its parent's updated by the move: the features of the
+// squares the move changed come off and go on. A perspective whose king moved starts again from
+// scratch, as every one of its features depends on where its king is.
+//
+// Updates are lazy. Most positions the search reaches are never evaluated (their evaluation is in
+// a table, or they are pruned first), so a move only records the position it leads to, and the
+// accumulators are brought up to date when a position is evaluated.
+pub struct Accumulators {
+    network: Arc<Network>,
+    // Per ply; entries past `len` are kept for reuse.
+    stack: Vec<Entry>,
+    len: usize,
+}
+
+struct Entry {
+    board: ArrayBoard,
+    // [white, black], if they have been brought up to date with `board`.
+    values: [Vec<i16>; 2],
+    computed: bool,
+}
+
+impl Accumulators {
+    // The accumulators for a search from `root`, if a network is loaded.
+    pub fn new(root: &ArrayBoard) -> Option<Accumulators> {
+        let network = network()?;
+        let mut values = [vec![0; network.hidden], vec![0; network.hidden]];
+        for (side, accumulator) in values.iter_mut().enumerate() {
+            network.refresh(root, side, accumulator);
+        }
+        let root = Entry {
+            board: *root,
+            values,
+            computed: true,
+        };
+        Some(Accumulators {
+            network,
+            stack: vec![root],
+            len: 1,
+        })
+    }
+
+    // Moves on to `child`, a position one move (or a null move) on from the current one.
+    pub fn push(&mut self, child: &ArrayBoard) {
+        if self.len == self.stack.len() {
+            let hidden = self.network.hidden;
+            self.stack.push(Entry {
+                board: *child,
+                values: [vec![0; hidden], vec![0; hidden]],
+                computed: false,
+            });
+        } else {
+            self.stack[self.len].board = *child;
+            self.stack[self.len].computed = false;
+        }
+        self.len += 1;
+    }
+
+    pub fn pop(&mut self) {
+        self.len -= 1;
+    }
+
+    // Brings the entry at `ply` up to date from the one before it, which is.
+    fn update(&mut self, ply: usize) {
+        let (done, rest) = self.stack.split_at_mut(ply);
+        let (previous, next) = (&done[ply - 1], &mut rest[0]);
+        let (parent, child) = (&previous.board, &next.board);
+        let (parent_kings, child_kings) = (king_squares(parent), king_squares(child));
+        for side in 0..2 {
+            if parent_kings[side] != child_kings[side] {
+                self.network.refresh(child, side, &mut next.values[side]);
+                continue;
+            }
+            let king = child_kings[side];
+            next.values[side].copy_from_slice(&previous.values[side]);
+            for sq in 0..64 {
+                let (before, after) = (parent.get_piece(sq), child.get_piece(sq));
+                if before == after {
+                    continue;
+                }
+                for (piece, update) in [
+                    (before, subtract_weights as fn(&mut [i16], &[i16])),
+                    (after, add_weights),
+                ] {
+                    if piece != 0 && piece_type(piece) != PieceType::King as u32 {
+                        update(
+                            &mut next.values[side],
+                            self.network.weights(feature(side, king, piece, sq)),
+                        );
+                    }
+                }
+            }
+        }
+        next.computed = true;
+    }
+
+    // The evaluation of the current position.
+    pub fn evaluate(&mut self) -> i64 {
+        let top = self.len - 1;
+        let first = (0..=top)
+            .rev()
+            .find(|&ply| self.stack[ply].computed)
+            .unwrap_or(0);
+        for ply in first + 1..=top {
+            self.update(ply);
+        }
+        let entry = &self.stack[top];
+        output(&self.network, &entry.board, &entry.values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HIDDEN: usize = 32;
+
+    // A network file with the given accumulator size and weights that vary with their place.
+    fn network_file(hidden: u32) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(hidden.to_le_bytes());
+        let count = (1 + FEATURES + 2) * hidden as usize;
+        for i in 0..count {
+            bytes.extend((((i * 7919) % 2001) as i16 - 1000).to_le_bytes());
+        }
+        bytes.extend((-1234i32).to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn network_round_trip() {
+        let bytes = network_file(HIDDEN as u32);
+        let network = Network::parse(&bytes).unwrap();
+        assert_eq!(network.hidden, HIDDEN);
+        let mut reader = Reader {
+            bytes: &bytes[12..],
+        };
+        assert_eq!(network.feature_biases, reader.i16s(HIDDEN).unwrap());
+        assert_eq!(
+            network.feature_weights,
+            reader.i16s(FEATURES * HIDDEN).unwrap()
+        );
+        assert_eq!(network.output_weights, reader.i16s(2 * HIDDEN).unwrap());
+        assert_eq!(network.output_bias, -1234);
+    }
+
+    #[test]
+    fn truncated_networks_are_rejected() {
+        let bytes = network_file(HIDDEN as u32);
+        for len in [
+            0,
+            5,
+            8,
+            11,
+            12,
+            100,
+            12 + HIDDEN * 2,
+            bytes.len() / 2,
+            bytes.len() - 1,
+        ] {
+            assert!(Network::parse(&bytes[..len]).is_err(), "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn garbled_networks_are_rejected() {
+        let mut bad_magic = network_file(HIDDEN as u32);
+        bad_magic[0] = b'X';
+        let mut too_long = network_file(HIDDEN as u32);
+        too_long.push(0);
+        // Accumulator sizes that are zero, not a whole number of lanes, too big, or wrong for the
+        // weights that follow.
+        let resized = |hidden: usize| {
+            let mut bytes = network_file(HIDDEN as u32);
+            bytes[8..12].copy_from_slice(&(hidden as u32).to_le_bytes());
+            bytes
+        };
+        for bytes in [
+            bad_magic,
+            too_long,
+            resized(0),
+            resized(HIDDEN + 1),
+            resized(MAX_HIDDEN + LANES),
+            resized(2 * HIDDEN),
+        ] {
+            assert!(Network::parse(&bytes).is_err());
+        }
+    }
+
+    #[test]
+    fn bad_files_load_no_network() {
+        let path = std::env::temp_dir().join("walrus-bot-truncated.nnue");
+        let bytes = network_file(HIDDEN as u32);
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        set_network_file(path.to_str().unwrap());
+        fs::remove_file(&path).ok();
+        assert!(network().is_none());
+        assert_eq!(network_key(), 0);
+    }
+}
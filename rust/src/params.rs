@@ -0,0 +1,86 @@
+// The search's tunable constants: pruning margins, reduction coefficients and the like. Each is a
+// UCI spin option, so that tuners (SPSA runs under OpenBench, say) can set them with `setoption`
+// between games. Values are integers; coefficients that aren't are kept in hundredths.
+use std::sync::atomic::{AtomicI64, Ordering};
+
+pub struct Param {
+    // The UCI option's name.
+    pub name: &'static str,
+    pub default: i64,
+    pub min: i64,
+    pub max: i64,
+    value: AtomicI64,
+}
+
+impl Param {
+    const fn new(name: &'static str, default: i64, min: i64, max: i64) -> Param {
+        Param {
+            name,
+            default,
+            min,
+            max,
+            value: AtomicI64::new(default),
+        }
+    }
+
+    pub fn get(&self) -> i64 {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    // Values outside the option's range are clamped into it.
+    pub fn set(&self, value: i64) {
+        self.value
+            .store(value.clamp(self.min, self.max), Ordering::Relaxed);
+    }
+}
+
+// Half-width of the first aspiration window, in centipawns.
+pub static ASPIRATION_WINDOW: Param = Param::new("AspirationWindow", 25, 5, 200);
+// Late move reductions are LMR_BASE + ln(depth) * ln(move number) / LMR_DIVISOR, in hundredths.
+pub static LMR_BASE: Param = Param::new("LmrBase", 75, 0, 300);
+pub static LMR_DIVISOR: Param = Param::new("LmrDivisor", 225, 100, 500);
+// Late move pruning keeps the first LMP_BASE + depth * depth moves.
+pub static LMP_BASE: Param = Param::new("LmpBase", 3, 1, 10);
+// Margins per ply of depth, and what being improving takes off or adds, in centipawns.
+pub static REVERSE_FUTILITY_MARGIN: Param = Param::new("ReverseFutilityMargin", 100, 20, 300);
+pub static REVERSE_FUTILITY_IMPROVING: Param = Param::new("ReverseFutilityImproving", 50, 0, 200);
+pub static RAZOR_MARGIN: Param = Param::new("RazorMargin", 300, 50, 800);
+pub static FUTILITY_MARGIN: Param = Param::new("FutilityMargin", 150, 20, 400);
+pub static FUTILITY_IMPROVING: Param = Param::new("FutilityImproving", 50, 0, 200);
+pub static SEE_PRUNE_MARGIN: Param = Param::new("SeePruneMargin", 100, 0, 400);
+// Null move reductions, below and above depth 6.
+pub static NULL_MOVE_REDUCTION: Param = Param::new("NullMoveReduction", 2, 1, 4);
+pub static NULL_MOVE_DEEP_REDUCTION: Param = Param::new("NullMoveDeepReduction", 3, 1, 5);
+// Dynamic contempt: at most DYNAMIC_CONTEMPT centipawns, half of it at a root score of
+// DYNAMIC_CONTEMPT_HALF. 0 leaves just the Contempt option.
+pub static DYNAMIC_CONTEMPT: Param = Param::new("DynamicContempt", 40, 0, 150);
+pub static DYNAMIC_CONTEMPT_HALF: Param = Param::new("DynamicContemptHalf", 200, 20, 1000);
+// The transposition table's replacement policy: how many plies of depth an entry is worth less for
+// each search it is old. The higher, the sooner entries from earlier searches make way for new
+// ones; 0 replaces by depth alone.
+pub static TT_AGE_WEIGHT: Param = Param::new("TTAgeWeight", 4, 0, 64);
+
+pub static ALL: [&Param; 15] = [
+    &ASPIRATION_WINDOW,
+    &LMR_BASE,
+    &LMR_DIVISOR,
+    &LMP_BASE,
+    &REVERSE_FUTILITY_MARGIN,
+    &REVERSE_FUTILITY_IMPROVING,
+    &RAZOR_MARGIN,
+    &FUTILITY_MARGIN,
+    &FUTILITY_IMPROVING,
+    &SEE_PRUNE_MARGIN,
+    &NULL_MOVE_REDUCTION,
+    &NULL_MOVE_DEEP_REDUCTION,
+    &DYNAMIC_CONTEMPT,
+    &DYNAMIC_CONTEMPT_HALF,
+    &TT_AGE_WEIGHT,
+];
+
+// The parameter whose option is called `name`, ignoring case as UCI option names do.
+pub fn find(name: &str) -> Option<&'static Param> {
+    ALL.iter()
+        .copied()
+        .find(|param| param.name.eq_ignore_ascii_case(name))
+}
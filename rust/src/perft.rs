@@ -0,0 +1,73 @@
+use super::arrayboard;
+use super::arrayboard::ArrayBoard;
+use std::time::Instant;
+
+pub fn perft(board: ArrayBoard, max_depth: u32, depth: u32) -> (u32, u32, u32, u32, u32) {
+    let (mut nodes, mut captures, mut castles, mut checks, mut promos) = (0, 0, 0, 0, 0);
+    if depth == (max_depth - 1) {
+        for mv in board.generate_moves() {
+            nodes += 1;
+            if mv.meta & arrayboard::generate_moves::MOVE_CAPTURE > 0 {
+                captures += 1;
+            }
+            if mv.meta & arrayboard::generate_moves::MOVE_CASTLE > 0 {
+                castles += 1;
+            }
+            if mv.meta & arrayboard::generate_moves::MOVE_CHECK > 0 {
+                checks += 1;
+            }
+            if mv.meta & arrayboard::generate_moves::MOVE_PROMO > 0 {
+                promos += 1;
+            }
+        }
+        return (nodes, captures, castles, checks, promos);
+    }
+    for mv in board.generate_moves() {
+        let new_board = board.make_move(&mv);
+        // send!("{}", &mv.to_string());
+        // new_board.pretty_print(true);
+        let (n, c1, c2, c3, p) = perft(new_board, max_depth, depth + 1);
+        nodes += n;
+        captures += c1;
+        castles += c2;
+        checks += c3;
+        promos += p;
+    }
+    (nodes, captures, castles, checks, promos)
+}
+
+// Prints the perft count below each legal move, then the total. Comparing these numbers against a
+// known-good engine narrows a move generation bug down to a single move.
+pub fn divide(board: ArrayBoard, depth: u32) -> u32 {
+    let start = Instant::now();
+    let mut total = 0;
+    for mv in board.generate_moves() {
+        let nodes = if depth <= 1 {
+            1
+        } else {
+            perft(board.make_move(&mv), depth - 1, 0).0
+        };
+        send!("{}: {nodes}", mv.to_string());
+        total += nodes;
+    }
+    send!();
+    send!("Nodes searched: {total}");
+    send!("Time: {}ms", start.elapsed().as_millis());
+    total
+}
+
+pub fn print_perft(board: ArrayBoard, depth: u32) {
+    let start = Instant::now();
+    let (nodes, captures, castles, checks, promos) = perft(board, depth, 0);
+    let tm = start.elapsed().as_millis() as u64;
+    send!(
+        "Perft({depth}) results: \n    \
+         nodes: {nodes}\n    \
+         captures: {captures}\n    \
+         castles: {castles}\n    \
+         checks: {checks}\n    \
+         promos: {promos}\n    \
+         {tm}ms,  {} nps",
+        nodes as u64 * 1000 / std::cmp::max(tm, 1)
+    );
+}
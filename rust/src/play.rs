@@ -0,0 +1,208 @@
+// Console mode for playing against the engine in a terminal, without a GUI. Started with
+// `walrus_bot play [white|black]`, or by sending `play [white|black]` as the first command.
+use super::arrayboard::{ArrayBoard, STARTING_FEN};
+use super::engine;
+use super::tt;
+use super::wdl;
+use std::io;
+use std::io::Write;
+use std::sync::atomic::Ordering;
+
+const HELP: &str = "Enter moves in SAN (Nf3, exd5, O-O, e8=Q) or UCI (g1f3) notation.
+Other commands:
+  undo      take back your last move
+  moves     list the legal moves
+  fen       print the position as FEN
+  new       start a new game
+  help      show this message
+  quit      leave";
+
+fn render(board: &ArrayBoard) {
+    println!();
+    board.pretty_print(false);
+    println!(" a b c d e f g h");
+    println!();
+}
+
+// The result of the game if it is over, in PGN style with the reason. `history` holds the Zobrist
+// keys of the positions before `board`, for spotting repetitions.
+fn game_result(board: &ArrayBoard, history: impl Iterator<Item = u64>) -> Option<&'static str> {
+    if board.is_insufficient_material() {
+        return Some("1/2-1/2 {Insufficient material}");
+    }
+    if board.generate_moves().is_empty() {
+        return Some(match (board.is_king_checked(), board.white_to_move()) {
+            (true, true) => "0-1 {Black mates}",
+            (true, false) => "1-0 {White mates}",
+            (false, _) => "1/2-1/2 {Stalemate}",
+        });
+    }
+    if board.halfmove_clock() >= 100 {
+        return Some("1/2-1/2 {Fifty-move rule}");
+    }
+    let key = board.zobrist_key();
+    if history.filter(|&seen| seen == key).count() >= 2 {
+        return Some("1/2-1/2 {Threefold repetition}");
+    }
+    None
+}
+
+pub fn run(args: &[&str]) {
+    engine::QUIET.store(true, Ordering::Relaxed);
+    let human_plays_white = args.first() != Some(&"black");
+    let mut board = ArrayBoard::create_from_fen(STARTING_FEN);
+    // Positions before each move played, for undo.
+    let mut history: Vec<ArrayBoard> = Vec::new();
+    println!(
+        "You play {}. Type 'help' for commands.",
+        if human_plays_white { "white" } else { "black" }
+    );
+    render(&board);
+    loop {
+        let keys = history.iter().map(ArrayBoard::zobrist_key);
+        let result = game_result(&board, keys.clone());
+        if result.is_none() && board.white_to_move() != human_plays_white {
+            println!("walrus-bot is thinking...");
+            engine::STOP.store(false, Ordering::Relaxed);
+            *engine::GAME_HISTORY.lock().unwrap() = keys.collect();
+            let limits = engine::SearchLimits {
+                depth: engine::DEFAULT_DEPTH,
+                ..Default::default()
+            };
+            let (best_move, _result) = engine::find_best_move(board, &limits);
+            if let Some(mv) = best_move.and_then(|mv| board.find_legal_move(&mv)) {
+                println!("walrus-bot plays {}", board.san(&mv));
+                history.push(board);
+                board = board.make_move(&mv);
+                render(&board);
+                let keys = history.iter().map(ArrayBoard::zobrist_key);
+                if let Some(result) = game_result(&board, keys) {
+                    println!("{result}");
+                }
+            }
+            continue;
+        }
+
+        print!("> ");
+        io::stdout().flush().ok();
+        let mut buffer = String::new();
+        match io::stdin().read_line(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => (),
+        }
+        match buffer.trim() {
+            "" => (),
+            "quit" | "exit" => break,
+            "help" => println!("{HELP}"),
+            "fen" => println!("{}", board.fen()),
+            "moves" => {
+                let moves: Vec<String> = board
+                    .generate_moves()
+                    .iter()
+                    .map(|mv| board.san(mv))
+                    .collect();
+                println!("{}", moves.join(" "));
+            }
+            "new" => {
+                board = ArrayBoard::create_from_fen(STARTING_FEN);
+                history.clear();
+                tt::clear();
+                render(&board);
+            }
+            // Back to the last position where it was the human's turn, taking back the engine's
+            // reply as well.
+            "undo" | "takeback" => {
+                let mut undone = false;
+                while let Some(previous) = history.pop() {
+                    board = previous;
+                    undone = true;
+                    if board.white_to_move() == human_plays_white {
+                        break;
+                    }
+                }
+                if undone {
+                    render(&board);
+                } else {
+                    println!("Nothing to take back.");
+                }
+            }
+            _ if result.is_some() => println!("The game is over. Type 'undo' or 'new'."),
+            mv => match board
+                .find_san_move(mv)
+                .or_else(|| board.find_legal_move(mv))
+            {
+                Some(bit_move) => {
+                    history.push(board);
+                    board = board.make_move(&bit_move);
+                    render(&board);
+                    let keys = history.iter().map(ArrayBoard::zobrist_key);
+                    if let Some(result) = game_result(&board, keys) {
+                        println!("{result}");
+                    }
+                }
+                None => println!("Illegal move: {mv}"),
+            },
+        }
+    }
+}
+
+// `walrus_bot selfplay [depth] [fen]`: plays the engine against itself, each move searched to
+// `depth` (DEFAULT_DEPTH by default), from `fen` or the starting position, and prints the game in
+// SAN with its result. Games that are clearly decided are adjudicated by the win/draw/loss model
+// rather than played out.
+pub fn selfplay(args: &[&str]) {
+    engine::QUIET.store(true, Ordering::Relaxed);
+    let depth = match args.first() {
+        Some(depth) => match depth.parse() {
+            Ok(depth) => depth,
+            Err(_) => {
+                println!("usage: walrus_bot selfplay [depth] [fen]");
+                return;
+            }
+        },
+        None => engine::DEFAULT_DEPTH,
+    };
+    let fen = match args.len() {
+        0 | 1 => STARTING_FEN.to_string(),
+        _ => args[1..].join(" "),
+    };
+    let mut board = match ArrayBoard::parse_fen(&fen) {
+        Ok(board) => board,
+        Err(e) => {
+            println!("invalid fen: {e}");
+            return;
+        }
+    };
+    tt::clear();
+    let mut history: Vec<u64> = Vec::new();
+    let mut adjudicator = wdl::Adjudicator::default();
+    let mut moves = Vec::new();
+    let result = loop {
+        if let Some(result) = game_result(&board, history.iter().copied()) {
+            break result;
+        }
+        engine::STOP.store(false, Ordering::Relaxed);
+        *engine::GAME_HISTORY.lock().unwrap() = history.clone();
+        let limits = engine::SearchLimits {
+            depth,
+            ..Default::default()
+        };
+        let (best_move, search) = engine::find_best_move(board, &limits);
+        if let Some(result) = adjudicator.update(&board, moves.len(), search.score) {
+            break result;
+        }
+        let Some(mv) = best_move.and_then(|mv| board.find_legal_move(&mv)) else {
+            break "* {No move}";
+        };
+        let number = match board.white_to_move() {
+            true => format!("{}. ", board.fullmove_number()),
+            false if moves.is_empty() => format!("{}... ", board.fullmove_number()),
+            false => String::new(),
+        };
+        moves.push(format!("{number}{}", board.san(&mv)));
+        history.push(board.zobrist_key());
+        board = board.make_move(&mv);
+    };
+    println!("{}", moves.join(" "));
+    println!("{result}");
+}
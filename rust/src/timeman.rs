@@ -0,0 +1,94 @@
+// Time management: turns the clock the GUI reports into how long to think about the move.
+//
+// A search gets two limits. The soft limit is the time it aims to use: once past it, no new
+// iteration is started, but the one in progress may finish. The hard limit is never exceeded: the
+// search is stopped where it stands, and plays the best move of its last completed iteration.
+use std::cmp;
+
+// Kept back from the clock for the time the move takes to reach the GUI.
+const MOVE_OVERHEAD_MS: u64 = 30;
+// Moves the remaining time is shared between when the GUI doesn't say (sudden death).
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+// How far past the soft limit an iteration may run.
+const HARD_LIMIT_FACTOR: u64 = 4;
+
+// The clock of the side to move, as given to `go`, in milliseconds.
+#[derive(Default)]
+pub struct Clock {
+    pub time: Option<u64>,
+    pub increment: u64,
+    pub movestogo: Option<u64>,
+    pub movetime: Option<u64>,
+}
+
+// How long a search may take, in milliseconds since it started.
+#[derive(Copy, Clone)]
+pub struct TimeLimits {
+    pub soft: u64,
+    pub hard: u64,
+    // Whether the search may scale the soft limit to how the search is going; see soft_scale.
+    // Not for a fixed movetime, which the GUI means to be used.
+    pub flexible: bool,
+}
+
+impl Default for TimeLimits {
+    // No limits at all.
+    fn default() -> TimeLimits {
+        TimeLimits {
+            soft: u64::MAX,
+            hard: u64::MAX,
+            flexible: false,
+        }
+    }
+}
+
+impl TimeLimits {
+    // The soft limit scaled by `percent`, within the hard limit.
+    pub fn scaled_soft(&self, percent: u64) -> u64 {
+        if !self.flexible {
+            return self.soft;
+        }
+        cmp::min(self.soft.saturating_mul(percent) / 100, self.hard)
+    }
+}
+
+// How much of the soft limit to use, in percent, from how the iterations so far went. A best move
+// that took almost all of the last iteration's nodes is an easy move: its alternatives were
+// refuted so quickly that more time is unlikely to change it. A best move that keeps changing is
+// the opposite, and gets more time. `best_move_changes` counts recent changes in hundredths,
+// decaying by half every iteration; `best_move_share` is in permille.
+pub fn soft_scale(best_move_share: u64, best_move_changes: u64) -> u64 {
+    let effort = match best_move_share {
+        900.. => 40,
+        700..=899 => 75,
+        _ => 100,
+    };
+    effort * cmp::min(100 + best_move_changes / 2, 250) / 100
+}
+
+// The limits for a move on `clock`. A clock without a time or movetime leaves the search
+// unlimited.
+pub fn allocate(clock: &Clock) -> TimeLimits {
+    if let Some(movetime) = clock.movetime {
+        let limit = cmp::max(movetime.saturating_sub(MOVE_OVERHEAD_MS), 1);
+        return TimeLimits {
+            soft: limit,
+            hard: limit,
+            flexible: false,
+        };
+    }
+    let Some(time) = clock.time else {
+        return TimeLimits::default();
+    };
+    let available = time.saturating_sub(MOVE_OVERHEAD_MS);
+    let moves = clock.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).clamp(1, 50);
+    // Spending the whole clock on the last move before the time control would be cutting it too
+    // fine, so the hard limit leaves a fifth.
+    let hard = cmp::max(available * 4 / 5, 1);
+    let soft = available / moves + clock.increment * 3 / 4;
+    TimeLimits {
+        soft: cmp::min(soft, hard),
+        hard: cmp::min(soft * HARD_LIMIT_FACTOR, hard),
+        flexible: true,
+    }
+}
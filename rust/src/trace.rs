@@ -0,0 +1,67 @@
+// Optional trace of the search tree (the `Search Trace File` option), for working out offline why
+// the search did what it did. Every search of a node below the root is written as a line
+//
+//   <depth> <alpha> <beta> <score> <move path from the root>
+//
+// after the search returns, so children come before their parents. Searches and iterations are
+// marked by lines starting with `search` and `iteration`. Traces grow by millions of lines a
+// second, so this is for short searches only.
+use super::arrayboard::BitMove;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+static TRACE_FILE: Mutex<Option<BufWriter<File>>> = Mutex::new(None);
+// Mirrors whether TRACE_FILE is open, so that the search can check without taking the lock.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+// Starts tracing to `path`, replacing what it held. An empty path (or "<empty>") turns tracing
+// off.
+pub fn set_trace_file(path: &str) {
+    let file = match path.trim() {
+        "" | "<empty>" => None,
+        path => match File::create(path) {
+            Ok(file) => Some(BufWriter::new(file)),
+            Err(e) => {
+                send!("info string cannot open trace file {path}: {e}");
+                None
+            }
+        },
+    };
+    ENABLED.store(file.is_some(), Ordering::Relaxed);
+    *TRACE_FILE.lock().unwrap() = file;
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn write_line(line: &str) {
+    if let Some(file) = TRACE_FILE.lock().unwrap().as_mut() {
+        writeln!(file, "{line}").ok();
+    }
+}
+
+// Marks the start of a search of the position `fen`.
+pub fn search_started(fen: &str) {
+    write_line(&format!("search {fen}"));
+}
+
+pub fn iteration_started(depth: u8) {
+    write_line(&format!("iteration {depth}"));
+}
+
+// Records a finished search of the node reached by `path`, as seen by its side to move.
+pub fn node(path: &[BitMove], depth: u8, alpha: i64, beta: i64, score: i64) {
+    let moves: Vec<String> = path.iter().map(|mv| mv.to_string()).collect();
+    let line = format!("{depth} {alpha} {beta} {score} {}", moves.join(" "));
+    write_line(line.trim_end());
+}
+
+// Writes out what is buffered, so that the trace of a finished search is complete on disk.
+pub fn flush() {
+    if let Some(file) = TRACE_FILE.lock().unwrap().as_mut() {
+        file.flush().ok();
+    }
+}
@@ -0,0 +1,256 @@
+// Transposition table: remembers the results of searched positions, keyed by their Zobrist key, so
+// that positions reached again by a different move order (or in a later iteration) needn't be
+// searched again.
+//
+// The table is lockless, so that several search threads can share it. Each slot is two atomic
+// words, and its key is stored xored with the data word: a slot that another thread was writing to
+// while it was read fails the key check and reads as empty, rather than as a torn entry. The table
+// itself is only ever replaced between searches, so probes and stores just follow a pointer to it.
+use super::arrayboard::BitMove;
+use super::params::TT_AGE_WEIGHT;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, AtomicU64, AtomicU8, Ordering};
+
+pub const DEFAULT_HASH_MB: usize = 16;
+pub const MAX_HASH_MB: usize = 1024;
+
+// How a stored score relates to the position's true score.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Bound {
+    // The search finished inside its window: the score is exact.
+    Exact,
+    // The search failed high: the true score is at least this.
+    Lower,
+    // The search failed low: the true score is at most this.
+    Upper,
+}
+
+#[derive(Copy, Clone)]
+pub struct Entry {
+    pub key: u64,
+    // Plies searched below the position.
+    pub depth: u8,
+    // Mate scores count the distance to mate from this position, not from the root.
+    pub score: i64,
+    pub bound: Bound,
+    pub best_move: Option<BitMove>,
+    // The position's static evaluation, so that searching it again needn't evaluate it again.
+    pub static_eval: i64,
+}
+
+// Layout of a slot's data word: the score in the low SCORE_BITS bits, then the depth, the bound (0
+// in an empty slot), the generation and the encoded best move (0 for none).
+const SCORE_BITS: u32 = 28;
+const DEPTH_SHIFT: u64 = 28;
+const BOUND_SHIFT: u64 = 36;
+const GENERATION_SHIFT: u64 = 38;
+const MOVE_SHIFT: u64 = 44;
+// Scores are clamped to what the score field holds. Only the edges of the root's infinite window
+// are beyond it, and clamping those just loosens the bound.
+const SCORE_LIMIT: i64 = (1 << (SCORE_BITS - 1)) - 1;
+// Generations are kept modulo 64.
+const GENERATION_MASK: u8 = 0b11_1111;
+// The check word holds the key xored with the data word in its high bits, and the static
+// evaluation, clamped to 16 bits, in the low EVAL_BITS. Some of the key's low bits go unchecked,
+// but those mostly pick the bucket anyway.
+const EVAL_BITS: u32 = 16;
+const EVAL_MASK: u64 = (1 << EVAL_BITS) - 1;
+
+#[derive(Default)]
+struct Slot {
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+// Bumped at the start of every search, and stamped on the entries it stores, so that entries left
+// over from earlier searches can be told apart and replaced first.
+static GENERATION: AtomicU8 = AtomicU8::new(0);
+
+fn generation() -> u8 {
+    GENERATION.load(Ordering::Relaxed) & GENERATION_MASK
+}
+
+impl Slot {
+    // The generation and depth of the stored entry, if any, without checking its key.
+    fn age_and_depth(&self) -> Option<(u8, u8)> {
+        let data = self.data.load(Ordering::Relaxed);
+        if (data >> BOUND_SHIFT) & 0b11 == 0 {
+            return None;
+        }
+        Some((
+            (data >> GENERATION_SHIFT) as u8 & GENERATION_MASK,
+            (data >> DEPTH_SHIFT) as u8,
+        ))
+    }
+
+    // The stored entry, if it is for the position with `key`.
+    fn load(&self, key: u64) -> Option<Entry> {
+        let check = self.check.load(Ordering::Relaxed);
+        let data = self.data.load(Ordering::Relaxed);
+        if (key ^ data ^ check) & !EVAL_MASK != 0 {
+            return None;
+        }
+        let bound = match (data >> BOUND_SHIFT) & 0b11 {
+            0 => return None,
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        Some(Entry {
+            key,
+            depth: (data >> DEPTH_SHIFT) as u8,
+            score: ((data << (64 - SCORE_BITS)) as i64) >> (64 - SCORE_BITS),
+            bound,
+            best_move: match (data >> MOVE_SHIFT) as u32 {
+                0 => None,
+                mv => Some(BitMove::decode(mv)),
+            },
+            static_eval: check as u16 as i16 as i64,
+        })
+    }
+
+    fn save(&self, entry: &Entry) {
+        let bound: u64 = match entry.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        let score = entry.score.clamp(-SCORE_LIMIT, SCORE_LIMIT) as u64 & ((1 << SCORE_BITS) - 1);
+        let data = score
+            | (entry.depth as u64) << DEPTH_SHIFT
+            | bound << BOUND_SHIFT
+            | (generation() as u64) << GENERATION_SHIFT
+            | entry.best_move.map_or(0, |mv| mv.encode() as u64) << MOVE_SHIFT;
+        let eval = entry.static_eval.clamp(i16::MIN as i64, i16::MAX as i64) as i16 as u16 as u64;
+        self.check
+            .store((entry.key ^ data) & !EVAL_MASK | eval, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+
+    fn clear(&self) {
+        self.check.store(0, Ordering::Relaxed);
+        self.data.store(0, Ordering::Relaxed);
+    }
+}
+
+// Entries are stored in buckets of four slots, a cache line's worth, all probed together; a new
+// entry replaces the bucket's least valuable one.
+const BUCKET_SIZE: usize = 4;
+
+#[derive(Default)]
+#[repr(align(64))]
+struct Bucket {
+    slots: [Slot; BUCKET_SIZE],
+}
+
+// The buckets, allocated by `resize`, or on first use at the default size. Null until then.
+static TT: AtomicPtr<Box<[Bucket]>> = AtomicPtr::new(ptr::null_mut());
+
+fn allocate(mb: usize) -> *mut Box<[Bucket]> {
+    let count = mb.clamp(1, MAX_HASH_MB) * 1024 * 1024 / mem::size_of::<Bucket>();
+    Box::into_raw(Box::new((0..count).map(|_| Bucket::default()).collect()))
+}
+
+fn table() -> &'static [Bucket] {
+    let mut table = TT.load(Ordering::Acquire);
+    if table.is_null() {
+        // Of threads racing to allocate the first table, the first to swap theirs in wins.
+        let new = allocate(DEFAULT_HASH_MB);
+        table = match TT.compare_exchange(ptr::null_mut(), new, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => new,
+            Err(current) => {
+                // SAFETY: `new` was never shared.
+                drop(unsafe { Box::from_raw(new) });
+                current
+            }
+        };
+    }
+    // SAFETY: only `resize` frees a table, and it isn't called while anything uses the table.
+    unsafe { &*table }
+}
+
+fn bucket(key: u64) -> &'static Bucket {
+    let table = table();
+    &table[(key % table.len() as u64) as usize]
+}
+
+// Sets the table's size, for the Hash option. Clears it. The old table is freed, so this mustn't
+// be called while a search runs.
+pub fn resize(mb: usize) {
+    let old = TT.swap(allocate(mb), Ordering::AcqRel);
+    if !old.is_null() {
+        // SAFETY: with no search running, nothing refers to the old table any more.
+        drop(unsafe { Box::from_raw(old) });
+    }
+}
+
+pub fn clear() {
+    table()
+        .iter()
+        .flat_map(|bucket| &bucket.slots)
+        .for_each(Slot::clear);
+}
+
+pub fn probe(key: u64) -> Option<Entry> {
+    bucket(key).slots.iter().find_map(|slot| slot.load(key))
+}
+
+// Starts a new generation, making everything stored so far replaceable.
+pub fn new_search() {
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+// An entry for the same position is overwritten, unless it is from the current search, was
+// searched deeper and the new score isn't exact. A new entry without a best move (from a fail-low)
+// keeps the stored one's. Otherwise the new entry takes an empty slot or the one whose entry is
+// shallowest, counting older generations as shallower by the TTAgeWeight option's plies each.
+pub fn store(mut entry: Entry) {
+    let generation = generation();
+    let age_weight = TT_AGE_WEIGHT.get() as i32;
+    let bucket = bucket(entry.key);
+    let same_position = bucket
+        .slots
+        .iter()
+        .find_map(|slot| slot.load(entry.key).map(|stored| (slot, stored)));
+    if let Some((slot, stored)) = same_position {
+        entry.best_move = entry.best_move.or(stored.best_move);
+        if let Some((stored_generation, depth)) = slot.age_and_depth()
+            && stored_generation == generation
+            && depth > entry.depth
+            && entry.bound != Bound::Exact
+        {
+            return;
+        }
+        slot.save(&entry);
+        return;
+    }
+    let victim = bucket
+        .slots
+        .iter()
+        .min_by_key(|slot| match slot.age_and_depth() {
+            None => i32::MIN,
+            Some((stored_generation, depth)) => {
+                let age = generation.wrapping_sub(stored_generation) & GENERATION_MASK;
+                depth as i32 - age_weight * age as i32
+            }
+        });
+    victim.unwrap().save(&entry);
+}
+
+// Permille of the table used by the current search, for `info hashfull`. Estimated from the first
+// thousand slots.
+pub fn hashfull() -> u32 {
+    let sample: Vec<&Slot> = table()
+        .iter()
+        .flat_map(|bucket| &bucket.slots)
+        .take(1000)
+        .collect();
+    let generation = generation();
+    let used = sample
+        .iter()
+        .filter(|slot| slot.age_and_depth().is_some_and(|(g, _)| g == generation))
+        .count();
+    (used * 1000 / sample.len()) as u32
+}
@@ -1,104 +1,620 @@
-use super::arrayboard::{ArrayBoard, BitMove, STARTING_FEN};
+use super::arrayboard::{ArrayBoard, BitMove, CHESS960, STARTING_FEN};
+use super::bench;
 use super::engine;
+use super::eval;
+use super::iolog;
+use super::nnue;
+use super::params;
+use super::perft;
+use super::timeman;
+use super::trace;
+use super::tt;
 use std::cmp;
+use std::fs;
 use std::io;
+use std::io::Write;
 use std::sync::atomic::Ordering;
-use std::time::Instant;
+use std::sync::mpsc;
+use std::thread;
 
-pub fn run() {
-    let mut board_opt: Option<ArrayBoard> = None;
-    loop {
+// Runs a search on its own thread so the input loop stays responsive. The thread owns printing
+// `bestmove`, so joining it guarantees the GUI has received the move it is owed.
+fn start_search(board: ArrayBoard, limits: engine::SearchLimits) -> thread::JoinHandle<()> {
+    engine::STOP.store(false, Ordering::Relaxed);
+    thread::spawn(move || {
+        let (best_move, result) = engine::find_best_move(board, &limits);
+        send!(
+            "info depth {} seldepth {} nodes {} time {} nps {}",
+            result.depth,
+            result.seldepth,
+            result.nodes,
+            result.time,
+            engine::nodes_per_second(result.nodes, result.time)
+        );
+        // The reply the pv expects is the move to ponder on.
+        let ponder_move = match result.pv.as_slice() {
+            [first, reply, ..] if best_move.as_ref() == Some(&first.to_string()) => {
+                format!(" ponder {}", reply.to_string())
+            }
+            _ => String::new(),
+        };
+        match best_move {
+            Some(mv) => send!("bestmove {mv}{ponder_move}"),
+            None => {
+                board.pretty_print(true);
+                send!("ERROR: no moves possible");
+            }
+        }
+        io::stdout().flush().ok();
+    })
+}
+
+// Aborts the in-flight search (if any) and waits for it to report its bestmove.
+fn stop_search(search: &mut Option<thread::JoinHandle<()>>) {
+    if let Some(handle) = search.take() {
+        engine::STOP.store(true, Ordering::Relaxed);
+        engine::PONDERING.store(false, Ordering::Relaxed);
+        handle.join().ok();
+    }
+}
+
+// The game as set up by the last `position` command. Kept so that a `position` command that only
+// adds moves to the previous one, as GUIs send during a game, replays just the new moves.
+struct Game {
+    fen: Option<String>,
+    // The moves that were applied: the position's move list up to any illegal move.
+    moves: Vec<String>,
+    board: ArrayBoard,
+    // Zobrist keys of the positions before `board`, oldest first, for repetition detection.
+    history: Vec<u64>,
+}
+
+impl Game {
+    fn new(fen: Option<String>) -> Result<Game, String> {
+        let board = ArrayBoard::parse_fen(fen.as_deref().unwrap_or(STARTING_FEN))?;
+        Ok(Game {
+            fen,
+            moves: Vec::new(),
+            board,
+            history: Vec::new(),
+        })
+    }
+
+    // Plays `moves` on from the current position, stopping at the first illegal one.
+    fn play(&mut self, moves: &[String]) {
+        for (i, mv) in moves.iter().enumerate() {
+            match self.board.find_legal_move(mv) {
+                Some(bit_move) => {
+                    self.history.push(self.board.zobrist_key());
+                    self.board = self.board.make_move(&bit_move);
+                    self.moves.push(mv.clone());
+                }
+                None => {
+                    send!(
+                        "info string illegal move {mv} (move {}), ignoring it and the {} move(s) \
+                         after it",
+                        self.moves.len() + 1,
+                        moves.len() - i - 1
+                    );
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// Parameters of a `go` command. Every field is optional, mirroring the UCI spec.
+#[derive(Default)]
+struct GoParams {
+    wtime: Option<u64>,
+    btime: Option<u64>,
+    winc: Option<u64>,
+    binc: Option<u64>,
+    movestogo: Option<u64>,
+    depth: Option<u8>,
+    nodes: Option<u64>,
+    mate: Option<u8>,
+    movetime: Option<u64>,
+    infinite: bool,
+    ponder: bool,
+    searchmoves: Vec<String>,
+}
+
+impl GoParams {
+    // The limits of the search for the side to move (`white` or not), with the MaxDepth and
+    // MultiPV options' values.
+    fn limits(self, white: bool, max_depth: u8, multipv: usize) -> engine::SearchLimits {
+        // A search with a clock or node limit, or none at all, goes as deep as those allow.
+        // MaxDepth is for plain `go`.
+        let limited = self.wtime.is_some()
+            || self.btime.is_some()
+            || self.movetime.is_some()
+            || self.nodes.is_some()
+            || self.infinite
+            || self.ponder;
+        let default_depth = if limited { engine::MAX_PLY } else { max_depth };
+        engine::SearchLimits {
+            depth: self.depth.unwrap_or(default_depth),
+            nodes: self.nodes,
+            clock: timeman::Clock {
+                time: if white { self.wtime } else { self.btime },
+                increment: if white { self.winc } else { self.binc }.unwrap_or(0),
+                movestogo: self.movestogo,
+                movetime: self.movetime,
+            },
+            multipv,
+            searchmoves: self.searchmoves,
+            mate: self.mate,
+            infinite: self.infinite,
+        }
+    }
+}
+
+enum Command {
+    Uci,
+    Debug(bool),
+    IsReady,
+    SetOption {
+        name: String,
+        value: String,
+    },
+    UciNewGame,
+    // A fen of None means the starting position.
+    Position {
+        fen: Option<String>,
+        moves: Vec<String>,
+    },
+    Go(GoParams),
+    Stop,
+    // The opponent played the move the engine was pondering on.
+    PonderHit,
+    Print,
+    Display,
+    Eval,
+    // Checks the evaluation's symmetry over the positions in a file of FENs, one per line, or
+    // over the bench positions.
+    EvalSymmetry(Option<String>),
+    Perft(u32),
+    Divide(u32),
+    Bench(u8),
+    Quit,
+}
+
+// True iff `token` looks like a move in long algebraic notation, e.g. "e2e4" or "a7a8q".
+fn is_move_token(token: &str) -> bool {
+    let b = token.as_bytes();
+    (b.len() == 4 || (b.len() == 5 && b"nbrq".contains(&b[4])))
+        && (b'a'..=b'h').contains(&b[0])
+        && (b'1'..=b'8').contains(&b[1])
+        && (b'a'..=b'h').contains(&b[2])
+        && (b'1'..=b'8').contains(&b[3])
+}
+
+// GUIs occasionally send negative clock times when flagging, so those are clamped to zero
+// rather than rejected.
+fn parse_number(token: &str) -> Option<u64> {
+    token.parse::<i64>().ok().map(|n| cmp::max(n, 0) as u64)
+}
+
+fn parse_go(args: &[&str]) -> GoParams {
+    let mut params = GoParams::default();
+    let mut i = 0;
+    while i < args.len() {
+        match args[i] {
+            "infinite" => params.infinite = true,
+            "ponder" => params.ponder = true,
+            "searchmoves" => {
+                while i + 1 < args.len() && is_move_token(args[i + 1]) {
+                    params.searchmoves.push(args[i + 1].to_string());
+                    i += 1;
+                }
+            }
+            keyword => {
+                // A missing or garbled value leaves the parameter unset and is not consumed, so
+                // it still gets a chance to be read as the next keyword.
+                let number = args.get(i + 1).and_then(|token| parse_number(token));
+                let small = number.map(|n| cmp::min(n, u8::MAX as u64) as u8);
+                match keyword {
+                    "wtime" => params.wtime = number,
+                    "btime" => params.btime = number,
+                    "winc" => params.winc = number,
+                    "binc" => params.binc = number,
+                    "movestogo" => params.movestogo = number,
+                    "depth" => params.depth = small,
+                    "nodes" => params.nodes = number,
+                    "mate" => params.mate = small,
+                    "movetime" => params.movetime = number,
+                    // Unknown tokens are skipped.
+                    _ => {
+                        i += 1;
+                        continue;
+                    }
+                }
+                if number.is_some() {
+                    i += 1;
+                }
+            }
+        }
+        i += 1;
+    }
+    params
+}
+
+// `setoption name <id> [value <x>]`. Both the id and the value may contain spaces.
+fn parse_setoption(args: &[&str]) -> Option<Command> {
+    let name_start = args.iter().position(|&t| t == "name")? + 1;
+    let value_start = args.iter().position(|&t| t == "value");
+    let name_end = value_start.unwrap_or(args.len());
+    if name_start >= name_end {
+        return None;
+    }
+    let value = match value_start {
+        Some(v) => args[v + 1..].join(" "),
+        None => String::new(),
+    };
+    Some(Command::SetOption {
+        name: args[name_start..name_end].join(" "),
+        value,
+    })
+}
+
+// `position [fen <fenstring> | startpos] [moves <move1> ... <movei>]`.
+fn parse_position(args: &[&str]) -> Option<Command> {
+    let moves_start = args.iter().position(|&t| t == "moves");
+    let setup = &args[..moves_start.unwrap_or(args.len())];
+    let fen = match setup.first() {
+        Some(&"startpos") | Some(&"sp") => None,
+        Some(&"fen") if setup.len() > 1 => Some(setup[1..].join(" ")),
+        _ => return None,
+    };
+    let moves = match moves_start {
+        Some(m) => args[m + 1..].iter().map(|t| t.to_string()).collect(),
+        None => Vec::new(),
+    };
+    Some(Command::Position { fen, moves })
+}
+
+// Tokenizes a line of GUI input. Leading unknown tokens are skipped, as the UCI spec asks, so
+// e.g. "joho debug on" is read as "debug on". Returns None for lines with no usable command.
+fn parse_command(line: &str) -> Option<Command> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    for (i, token) in tokens.iter().enumerate() {
+        let args = &tokens[i + 1..];
+        return match *token {
+            "uci" => Some(Command::Uci),
+            "debug" => match args.first() {
+                Some(&"on") => Some(Command::Debug(true)),
+                Some(&"off") => Some(Command::Debug(false)),
+                _ => None,
+            },
+            "isready" => Some(Command::IsReady),
+            "setoption" => parse_setoption(args),
+            "ucinewgame" => Some(Command::UciNewGame),
+            "p" | "position" => parse_position(args),
+            "go" => Some(Command::Go(parse_go(args))),
+            "stop" => Some(Command::Stop),
+            "ponderhit" => Some(Command::PonderHit),
+            "print" => Some(Command::Print),
+            "d" => Some(Command::Display),
+            "eval" => Some(Command::Eval),
+            "evalsym" => Some(Command::EvalSymmetry(
+                (!args.is_empty()).then(|| args.join(" ")),
+            )),
+            "perft" => match args {
+                ["divide", depth, ..] => depth.parse().ok().map(Command::Divide),
+                [depth, ..] => depth.parse().ok().map(Command::Perft),
+                _ => None,
+            },
+            "divide" => match args {
+                [depth, ..] => depth.parse().ok().map(Command::Divide),
+                _ => None,
+            },
+            "bench" => Some(Command::Bench(
+                args.first()
+                    .and_then(|depth| depth.parse().ok())
+                    .unwrap_or(bench::DEFAULT_BENCH_DEPTH),
+            )),
+            "exit" | "end" | "quit" => Some(Command::Quit),
+            _ => continue,
+        };
+    }
+    None
+}
+
+// Stockfish-style `d`: the board plus everything needed to reproduce the position.
+fn display(board: &ArrayBoard) {
+    board.pretty_print(false);
+    send!();
+    send!(
+        "Side to move: {}",
+        if board.white_to_move() {
+            "white"
+        } else {
+            "black"
+        }
+    );
+    send!("Castling: {}", board.castling_rights());
+    send!(
+        "En passant: {}",
+        board.enpassant_square().unwrap_or(String::from("-"))
+    );
+    send!("Fen: {}", board.fen());
+    send!("Key: {:016X}", board.zobrist_key());
+}
+
+// Reads stdin on a dedicated thread and forwards each command to the command loop. `isready` is
+// answered right here, so the GUI gets its `readyok` even while the command loop is busy.
+// `first_line` is input that was already read while choosing the protocol.
+fn spawn_input_reader(first_line: String) -> mpsc::Receiver<Command> {
+    let (sender, receiver) = mpsc::channel();
+    let mut pending = Some(first_line);
+    thread::spawn(move || loop {
         let mut buffer = String::new();
-        let result = io::stdin().read_line(&mut buffer);
-        if result.is_err() {
-            println!("{:?}", result.err());
+        if let Some(line) = pending.take() {
+            buffer = line;
+        } else {
+            match io::stdin().read_line(&mut buffer) {
+                // EOF: the GUI has gone away, so shut down as if it had sent `quit`.
+                Ok(0) => buffer = String::from("quit"),
+                Ok(_) => (),
+                Err(e) => send!("{:?}", e),
+            }
         }
-        let instructions: Vec<&str> = buffer.split_whitespace().collect();
-        if instructions.len() == 0 {
-            continue;
+        iolog::log_input(&buffer);
+        let command = match parse_command(&buffer) {
+            Some(Command::IsReady) => {
+                send!("readyok");
+                continue;
+            }
+            Some(command) => command,
+            None => continue,
+        };
+        let is_quit = matches!(command, Command::Quit);
+        if sender.send(command).is_err() || is_quit {
+            break;
         }
-        match instructions[0] {
-            "uci" => {
-                println!("id name walrus-bot");
-                println!("id author The Walrus");
-                println!("option name MaxDepth type spin default 5 min 1 max 10");
-                println!("uciok");
-            }
-            "setoption" => {
-                if instructions[1] == "name"
-                    && instructions[2] == "MaxDepth"
-                    && instructions[3] == "value"
-                {
-                    engine::MAX_DEPTH.store(
-                        instructions[4].parse::<u8>().unwrap_or_default(),
-                        Ordering::Relaxed,
+    });
+    receiver
+}
+
+pub fn run(first_line: String) {
+    let mut board_opt: Option<ArrayBoard> = None;
+    let mut game: Option<Game> = None;
+    let mut search: Option<thread::JoinHandle<()>> = None;
+    let mut max_depth = engine::DEFAULT_DEPTH;
+    let mut multipv = 1;
+    let input = spawn_input_reader(first_line);
+    loop {
+        let command = input.recv().unwrap_or(Command::Quit);
+        match command {
+            Command::Uci => {
+                send!("id name walrus-bot");
+                send!("id author The Walrus");
+                send!(
+                    "option name MaxDepth type spin default {} min 1 max {}",
+                    engine::DEFAULT_DEPTH,
+                    engine::MAX_PLY
+                );
+                send!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    tt::DEFAULT_HASH_MB,
+                    tt::MAX_HASH_MB
+                );
+                send!("option name Clear Hash type button");
+                send!("option name MultiPV type spin default 1 min 1 max 256");
+                send!("option name Contempt type spin default 0 min -100 max 100");
+                send!("option name UCI_ShowRefutations type check default false");
+                send!("option name UCI_Chess960 type check default false");
+                send!("option name UCI_AnalyseMode type check default false");
+                send!("option name UCI_ShowWDL type check default false");
+                send!("option name NormalizeScore type check default false");
+                send!("option name Deterministic type check default false");
+                send!("option name Log File type string default <empty>");
+                send!("option name Search Trace File type string default <empty>");
+                send!("option name EvalFile type string default <empty>");
+                for param in params::ALL {
+                    send!(
+                        "option name {} type spin default {} min {} max {}",
+                        param.name,
+                        param.default,
+                        param.min,
+                        param.max
                     );
                 }
+                send!("uciok");
             }
-            "ucinewgame" => {
-                println!("unimplemented");
-            }
-            "isready" => {
-                println!("readyok");
-            }
-            "p" | "position" => {
-                board_opt = match instructions[1] {
-                    "fen" => Some(ArrayBoard::create_from_fen(
-                        instructions[2..].join(" ").as_str(),
-                    )),
-                    "sp" | "startpos" => {
-                        let mut nb = ArrayBoard::create_from_fen(STARTING_FEN);
-                        if instructions.len() > 3 {
-                            nb = instructions[3..].iter().fold(nb, |board_acc, mv| {
-                                board_acc.make_move(&BitMove::from_string(mv))
-                            });
-                            // for mv in instructions[3..].iter() {
-                            //     new_board = new_board.make_move(&BitMove::from_string(mv));
-                            // }
+            Command::SetOption { name, value } => match name.to_lowercase().as_str() {
+                "maxdepth" => {
+                    max_depth = value
+                        .parse::<u8>()
+                        .unwrap_or(max_depth)
+                        .clamp(1, engine::MAX_PLY)
+                }
+                "multipv" => multipv = value.parse::<usize>().unwrap_or(multipv).clamp(1, 256),
+                "hash" => {
+                    if let Ok(mb) = value.parse() {
+                        stop_search(&mut search);
+                        tt::resize(mb);
+                    }
+                }
+                "clear hash" => {
+                    stop_search(&mut search);
+                    tt::clear();
+                }
+                "contempt" => {
+                    if let Ok(contempt) = value.parse::<i64>() {
+                        engine::CONTEMPT.store(contempt.clamp(-100, 100), Ordering::Relaxed);
+                    }
+                }
+                "uci_showrefutations" => {
+                    engine::SHOW_REFUTATIONS.store(value == "true", Ordering::Relaxed)
+                }
+                "uci_chess960" => CHESS960.store(value == "true", Ordering::Relaxed),
+                "uci_analysemode" => engine::ANALYSE_MODE.store(value == "true", Ordering::Relaxed),
+                "deterministic" => engine::DETERMINISTIC.store(value == "true", Ordering::Relaxed),
+                "log file" => iolog::set_log_file(&value),
+                "search trace file" => {
+                    stop_search(&mut search);
+                    trace::set_trace_file(&value);
+                }
+                "evalfile" => {
+                    stop_search(&mut search);
+                    nnue::set_network_file(&value);
+                }
+                "uci_showwdl" => engine::SHOW_WDL.store(value == "true", Ordering::Relaxed),
+                "normalizescore" => {
+                    engine::NORMALIZE_SCORE.store(value == "true", Ordering::Relaxed)
+                }
+                _ => {
+                    if let (Some(param), Ok(value)) = (params::find(&name), value.parse()) {
+                        param.set(value);
+                    }
+                }
+            },
+            Command::Debug(on) => engine::UCI_DEBUG.store(on, Ordering::Relaxed),
+            Command::IsReady => send!("readyok"),
+            Command::UciNewGame => {
+                stop_search(&mut search);
+                tt::clear();
+            }
+            Command::Position { fen, moves } => {
+                // Only moves after those already played need applying, if the game still starts
+                // from the same position.
+                let played = match &game {
+                    Some(g) if g.fen == fen && moves.starts_with(&g.moves) => g.moves.len(),
+                    _ => {
+                        match Game::new(fen) {
+                            Ok(g) => game = Some(g),
+                            Err(e) => {
+                                // Better to have no position than to quietly search the previous
+                                // one.
+                                send!("info string invalid fen: {e}");
+                                game = None;
+                                board_opt = None;
+                                continue;
+                            }
                         }
-                        Some(nb)
+                        0
                     }
-                    _ => None,
                 };
+                let g = game.as_mut().unwrap();
+                g.play(&moves[played..]);
+                board_opt = Some(g.board);
             }
-            "go" => {
+            Command::Go(params) => {
                 match board_opt {
                     Some(board) => {
-                        let start = Instant::now();
-                        let (best, _score, _mate_in, nodes) = engine::search(
-                            board,
-                            /* alpha= */ i32::MIN as i64,
-                            /* beta= */ i32::MAX as i64,
-                            /* depth=*/ 0,
-                        );
-                        if best.is_empty() {
-                            board.pretty_print(true);
-                            println!("ERROR: no moves possible");
-                        } else {
-                            println!("bestmove {}", best.split_whitespace().nth(0).unwrap());
+                        stop_search(&mut search);
+                        if let Some(g) = &game {
+                            *engine::GAME_HISTORY.lock().unwrap() = g.history.clone();
                         }
-                        let tm = start.elapsed().as_millis();
-                        println!(
-                            "info nodes {nodes} time {tm} nps {:3}",
-                            nodes as f64 / (tm as f64 / 1000.0)
-                        );
+                        engine::PONDERING.store(params.ponder, Ordering::Relaxed);
+                        let limits = params.limits(board.white_to_move(), max_depth, multipv);
+                        search = Some(start_search(board, limits));
                     }
-                    None => println!("ERROR: No board has been initialized yet. Use 'position'."),
+                    None => send!("ERROR: No board has been initialized yet. Use 'position'."),
                 };
             }
-            "print" => {
+            Command::Stop => stop_search(&mut search),
+            // The ponder search carries on as the real one, keeping everything it has searched.
+            Command::PonderHit => engine::PONDERING.store(false, Ordering::Relaxed),
+            Command::Print => {
                 match board_opt {
                     Some(b) => {
                         b.pretty_print(true);
                         b.print_legal_moves(false);
                     }
-                    None => println!("ERROR: No board has been initialized yet. Use 'position'."),
+                    None => send!("ERROR: No board has been initialized yet. Use 'position'."),
                 };
             }
-            "exit" => break,
-            "end" => break,
-            "quit" => break,
-            _ => (),
+            Command::Display => {
+                match board_opt {
+                    Some(b) => display(&b),
+                    None => send!("ERROR: No board has been initialized yet. Use 'position'."),
+                };
+            }
+            Command::Eval => {
+                match board_opt {
+                    Some(b) => eval::print_eval(b),
+                    None => send!("ERROR: No board has been initialized yet. Use 'position'."),
+                };
+            }
+            Command::EvalSymmetry(None) => eval::symmetry_test(&bench::BENCH_FENS),
+            Command::EvalSymmetry(Some(path)) => match fs::read_to_string(&path) {
+                Ok(contents) => {
+                    let fens: Vec<&str> = contents
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty())
+                        .collect();
+                    eval::symmetry_test(&fens);
+                }
+                Err(e) => send!("ERROR: cannot read {path}: {e}"),
+            },
+            Command::Perft(depth) | Command::Divide(depth) if depth == 0 => {
+                send!("ERROR: perft depth must be at least 1")
+            }
+            Command::Perft(depth) => {
+                match board_opt {
+                    Some(b) => perft::print_perft(b, depth),
+                    None => send!("ERROR: No board has been initialized yet. Use 'position'."),
+                };
+            }
+            Command::Divide(depth) => {
+                match board_opt {
+                    Some(b) => {
+                        perft::divide(b, depth);
+                    }
+                    None => send!("ERROR: No board has been initialized yet. Use 'position'."),
+                };
+            }
+            Command::Bench(depth) => {
+                stop_search(&mut search);
+                bench::run(depth);
+            }
+            Command::Quit => {
+                stop_search(&mut search);
+                break;
+            }
         }
     }
+    io::stdout().flush().ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::kpk;
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+
+    // An ending simple enough that even an unoptimized build gets well past the default depth in
+    // the time it is given.
+    const ENDGAME_FEN: &str = "8/5k2/3p4/8/3P4/5K2/8/8 w - - 0 1";
+
+    #[test]
+    fn timed_search_goes_past_default_depth() {
+        // The search of the ending reaches king and pawn against king, whose bitbase is built the
+        // first time it is probed; that isn't what is being timed.
+        kpk::probe(&ArrayBoard::create_from_fen(
+            "8/8/8/4k3/8/8/4P3/4K3 w - - 0 1",
+        ));
+        let limits =
+            parse_go(&["wtime", "30000", "btime", "30000"]).limits(true, engine::DEFAULT_DEPTH, 1);
+        assert_eq!(limits.depth, engine::MAX_PLY);
+        let board = ArrayBoard::create_from_fen(ENDGAME_FEN);
+        let result = engine::search(board, &limits, &AtomicBool::new(false));
+        assert!(
+            result.depth > engine::DEFAULT_DEPTH,
+            "depth {}",
+            result.depth
+        );
+    }
+
+    #[test]
+    fn plain_go_searches_to_max_depth() {
+        let limits = parse_go(&[]).limits(true, engine::DEFAULT_DEPTH, 1);
+        assert_eq!(limits.depth, engine::DEFAULT_DEPTH);
+        let limits = parse_go(&["depth", "3", "movetime", "1000"]).limits(true, 9, 1);
+        assert_eq!(limits.depth, 3);
+    }
 }
@@ -0,0 +1,108 @@
+// Win/draw/loss model: the chances of winning, drawing and losing that a score stands for, for
+// `UCI_ShowWDL`, for scores normalised to them (the `NormalizeScore` option) and for adjudicating
+// self-play games.
+//
+// The chance of winning is logistic in the score: a score of `center` wins half the time, and
+// `spread` is how quickly the chance grows past that. Both depend on the material left, as the
+// same advantage is worth more with fewer pieces to hold it off. The chance of losing is that of
+// the other side winning, and the rest is the chance of a draw. The coefficients agree, at the
+// material of a typical middlegame, with the chances the engine reported before they depended on
+// material: even at 100 cp, with a spread of 40.
+use super::arrayboard::{piece_type, ArrayBoard, PieceType};
+use super::engine;
+
+// Material, counted as pawn 1, knight and bishop 3, rook 5 and queen 9 for both sides, is clamped
+// to this range, and the model is in terms of it over MATERIAL_REFERENCE.
+const MATERIAL_MIN: i64 = 17;
+const MATERIAL_MAX: i64 = 78;
+const MATERIAL_REFERENCE: f64 = 58.0;
+// center = CENTER[0] + CENTER[1] * material / MATERIAL_REFERENCE, and likewise for spread.
+const CENTER: [f64; 2] = [60.0, 40.0];
+const SPREAD: [f64; 2] = [28.0, 12.0];
+
+// Adjudication: a game is won once the winning side's chances have been at least
+// WIN_THRESHOLD permille for WIN_PLIES searches in a row, and drawn once the chances of a draw
+// have been at least DRAW_THRESHOLD permille for DRAW_PLIES searches in a row from DRAW_MIN_PLY on.
+const WIN_THRESHOLD: u32 = 975;
+const WIN_PLIES: usize = 6;
+const DRAW_THRESHOLD: u32 = 800;
+const DRAW_PLIES: usize = 10;
+const DRAW_MIN_PLY: usize = 80;
+
+pub fn material(board: &ArrayBoard) -> i64 {
+    (0..64)
+        .map(|sq| match piece_type(board.get_piece(sq)) {
+            t if t == PieceType::Pawn as u32 => 1,
+            t if t == PieceType::Knight as u32 || t == PieceType::Bishop as u32 => 3,
+            t if t == PieceType::Rook as u32 => 5,
+            t if t == PieceType::Queen as u32 => 9,
+            _ => 0,
+        })
+        .sum()
+}
+
+// The model's center and spread with `material` on the board.
+fn parameters(material: i64) -> (f64, f64) {
+    let m = material.clamp(MATERIAL_MIN, MATERIAL_MAX) as f64 / MATERIAL_REFERENCE;
+    (CENTER[0] + CENTER[1] * m, SPREAD[0] + SPREAD[1] * m)
+}
+
+// The chances, in permille, of winning, drawing and losing with `score` (not a mate) for the side
+// to move and `material` on the board.
+pub fn score_to_wdl(score: i64, material: i64) -> (u32, u32, u32) {
+    let (center, spread) = parameters(material);
+    let win_rate = |cp: f64| (1000.0 / (1.0 + ((center - cp) / spread).exp())) as u32;
+    let win = win_rate(score as f64);
+    let loss = win_rate(-score as f64);
+    (win, 1000 - win - loss, loss)
+}
+
+// `score` rescaled so that 100 is an even chance of winning, whatever the material.
+pub fn normalize(score: i64, material: i64) -> i64 {
+    let (center, _) = parameters(material);
+    (score as f64 * 100.0 / center).round() as i64
+}
+
+// Follows a self-play game search by search, to end it once the result is clear.
+#[derive(Default)]
+pub struct Adjudicator {
+    // Searches in a row that one side has been winning, and which side ([white, black]).
+    win_plies: usize,
+    winner: usize,
+    draw_plies: usize,
+}
+
+impl Adjudicator {
+    // Records the `score` of a search of `board`, the game's position after `ply` plies, for its
+    // side to move. Returns the game's result, PGN style, once it can be adjudicated.
+    pub fn update(&mut self, board: &ArrayBoard, ply: usize, score: i64) -> Option<&'static str> {
+        let (win, draw, loss) = engine::score_to_wdl(score, material(board));
+        let side_to_move = !board.white_to_move() as usize;
+        let leader = if win >= loss {
+            side_to_move
+        } else {
+            1 - side_to_move
+        };
+        if win.max(loss) >= WIN_THRESHOLD {
+            if self.win_plies == 0 || self.winner != leader {
+                self.win_plies = 0;
+                self.winner = leader;
+            }
+            self.win_plies += 1;
+        } else {
+            self.win_plies = 0;
+        }
+        if draw >= DRAW_THRESHOLD && ply >= DRAW_MIN_PLY {
+            self.draw_plies += 1;
+        } else {
+            self.draw_plies = 0;
+        }
+        if self.win_plies >= WIN_PLIES {
+            return Some(match self.winner {
+                0 => "1-0 {White wins by adjudication}",
+                _ => "0-1 {Black wins by adjudication}",
+            });
+        }
+        (self.draw_plies >= DRAW_PLIES).then_some("1/2-1/2 {Draw by adjudication}")
+    }
+}
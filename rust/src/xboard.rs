@@ -0,0 +1,304 @@
+// Driver for the xboard/WinBoard protocol (CECP), for interfaces that don't speak UCI. Selected
+// by main when the first command received is `xboard`.
+use super::arrayboard::{ArrayBoard, STARTING_FEN};
+use super::engine;
+use super::iolog;
+use super::timeman;
+use super::tt;
+use std::io;
+use std::io::Write;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::thread;
+
+// Everything the command loop waits on: lines from the GUI, and moves from finished searches.
+enum Event {
+    Input(String),
+    // The search id and its best move, if any.
+    EngineMove(u64, Option<String>),
+}
+
+fn spawn_input_reader(sender: mpsc::Sender<Event>) {
+    thread::spawn(move || loop {
+        let mut buffer = String::new();
+        match io::stdin().read_line(&mut buffer) {
+            // EOF: the GUI has gone away, so shut down as if it had sent `quit`.
+            Ok(0) => buffer = String::from("quit"),
+            Ok(_) => (),
+            Err(e) => send!("{:?}", e),
+        }
+        iolog::log_input(&buffer);
+        let is_quit = buffer.trim() == "quit";
+        if sender.send(Event::Input(buffer)).is_err() || is_quit {
+            break;
+        }
+    });
+}
+
+// The time control as set by `level` or `st`, and the engine's clock as last reported by `time`.
+#[derive(Default, Clone)]
+struct TimeControl {
+    // Moves per time control, from `level`; 0 for all the game's moves.
+    moves_per_session: u64,
+    // In milliseconds.
+    increment: u64,
+    // A fixed time per move, from `st`.
+    move_time: Option<u64>,
+    time: Option<u64>,
+}
+
+impl TimeControl {
+    // `level MPS BASE INC`: BASE is in minutes or minutes:seconds, INC in seconds. The clock
+    // starts at the base time, until `time` reports it.
+    fn set_level(&mut self, args: &[&str]) {
+        let [moves, base, increment] = args else {
+            return;
+        };
+        let (minutes, seconds) = base.split_once(':').unwrap_or((base, "0"));
+        if let (Ok(moves), Ok(minutes), Ok(seconds), Ok(increment)) = (
+            moves.parse(),
+            minutes.parse::<u64>(),
+            seconds.parse::<u64>(),
+            increment.parse::<f64>(),
+        ) {
+            self.moves_per_session = moves;
+            self.time = Some((minutes * 60 + seconds) * 1000);
+            self.increment = (increment * 1000.0) as u64;
+            self.move_time = None;
+        }
+    }
+
+    // The clock of a search of `board`, in the terms time management takes.
+    fn clock(&self, board: &ArrayBoard) -> timeman::Clock {
+        // The moves still to play before the next time control. Counted from the start of the
+        // game, as xboard counts them.
+        let movestogo = (self.moves_per_session > 0).then(|| {
+            let played = (board.fullmove_number() as u64).saturating_sub(1);
+            self.moves_per_session - played % self.moves_per_session
+        });
+        timeman::Clock {
+            time: self.time,
+            increment: self.increment,
+            movestogo,
+            movetime: self.move_time,
+        }
+    }
+}
+
+struct Game {
+    board: ArrayBoard,
+    // Positions before each move played, for undo/remove.
+    history: Vec<ArrayBoard>,
+    // In force mode the engine only plays moves it is sent.
+    force: bool,
+    engine_plays_white: bool,
+    // Set by `sd`. Without it, a search with a clock searches as deep as the clock allows.
+    max_depth: Option<u8>,
+    time_control: TimeControl,
+    search: Option<thread::JoinHandle<()>>,
+    // Identifies the in-flight search, so that moves from abandoned searches are dropped.
+    search_id: u64,
+}
+
+impl Game {
+    fn new() -> Game {
+        Game {
+            board: ArrayBoard::create_from_fen(STARTING_FEN),
+            history: Vec::new(),
+            force: false,
+            engine_plays_white: false,
+            max_depth: None,
+            time_control: TimeControl::default(),
+            search: None,
+            search_id: 0,
+        }
+    }
+
+    fn start_search(&mut self, events: &mpsc::Sender<Event>) {
+        self.search_id += 1;
+        let (id, board, events) = (self.search_id, self.board, events.clone());
+        engine::STOP.store(false, Ordering::Relaxed);
+        *engine::GAME_HISTORY.lock().unwrap() = self
+            .history
+            .iter()
+            .map(|board| board.zobrist_key())
+            .collect();
+        let clock = self.time_control.clock(&self.board);
+        let timed = clock.time.is_some() || clock.movetime.is_some();
+        let default_depth = if timed {
+            engine::MAX_PLY
+        } else {
+            engine::DEFAULT_DEPTH
+        };
+        let limits = engine::SearchLimits {
+            depth: self.max_depth.unwrap_or(default_depth),
+            clock,
+            ..Default::default()
+        };
+        self.search = Some(thread::spawn(move || {
+            let (best_move, _result) = engine::find_best_move(board, &limits);
+            events.send(Event::EngineMove(id, best_move)).ok();
+        }));
+    }
+
+    // Stops the search and waits for it to finish. Its move is still delivered as an event; pass
+    // `discard` to have it ignored.
+    fn stop_search(&mut self, discard: bool) {
+        if let Some(handle) = self.search.take() {
+            engine::STOP.store(true, Ordering::Relaxed);
+            handle.join().ok();
+        }
+        if discard {
+            self.search_id += 1;
+        }
+    }
+
+    fn engine_to_move(&self) -> bool {
+        !self.force && self.board.white_to_move() == self.engine_plays_white
+    }
+
+    fn play(&mut self, mv: &str) -> bool {
+        match self.board.find_legal_move(mv) {
+            Some(bit_move) => {
+                self.history.push(self.board);
+                self.board = self.board.make_move(&bit_move);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Announces the result if the side to move has no legal moves, or neither side can mate.
+    fn check_game_over(&mut self) {
+        let result = if self.board.is_insufficient_material() {
+            "1/2-1/2 {Insufficient material}"
+        } else if !self.board.generate_moves().is_empty() {
+            return;
+        } else {
+            match (self.board.is_king_checked(), self.board.white_to_move()) {
+                (true, true) => "0-1 {Black mates}",
+                (true, false) => "1-0 {White mates}",
+                (false, _) => "1/2-1/2 {Stalemate}",
+            }
+        };
+        send!("{result}");
+        self.force = true;
+    }
+}
+
+pub fn run() {
+    engine::XBOARD_MODE.store(true, Ordering::Relaxed);
+    let (sender, events) = mpsc::channel();
+    spawn_input_reader(sender.clone());
+    let mut game = Game::new();
+    loop {
+        let line = match events.recv() {
+            Ok(Event::Input(line)) => line,
+            Ok(Event::EngineMove(id, best_move)) => {
+                if id != game.search_id {
+                    continue;
+                }
+                game.search = None;
+                if let Some(mv) = best_move {
+                    game.play(&mv);
+                    send!("move {mv}");
+                    game.check_game_over();
+                }
+                io::stdout().flush().ok();
+                continue;
+            }
+            Err(_) => String::from("quit"),
+        };
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let args = tokens.get(1..).unwrap_or_default();
+        match tokens.first().copied() {
+            None | Some("xboard") | Some("accepted") | Some("rejected") | Some("random")
+            | Some("hard") | Some("easy") | Some("computer") => (),
+            Some("level") => game.time_control.set_level(args),
+            Some("st") => {
+                if let Some(seconds) = args.first().and_then(|s| s.parse::<f64>().ok()) {
+                    game.time_control.move_time = Some((seconds * 1000.0) as u64);
+                }
+            }
+            // In centiseconds.
+            Some("time") => {
+                if let Some(time) = args.first().and_then(|t| t.parse::<i64>().ok()) {
+                    game.time_control.time = Some(time.max(0) as u64 * 10);
+                }
+            }
+            // The opponent's clock, which time management doesn't look at (nor does it for UCI).
+            Some("otim") => (),
+            Some("protover") => send!(
+                "feature myname=\"walrus-bot\" setboard=1 usermove=1 ping=1 colors=0 \
+                 sigint=0 sigterm=0 analyze=0 done=1"
+            ),
+            Some("new") => {
+                game.stop_search(true);
+                tt::clear();
+                game = Game {
+                    max_depth: game.max_depth,
+                    time_control: game.time_control.clone(),
+                    search_id: game.search_id,
+                    ..Game::new()
+                };
+            }
+            Some("force") | Some("result") => {
+                game.stop_search(true);
+                game.force = true;
+            }
+            Some("go") => {
+                game.stop_search(true);
+                game.force = false;
+                game.engine_plays_white = game.board.white_to_move();
+                game.start_search(&sender);
+            }
+            // Move now: the search's best move so far arrives as an event.
+            Some("?") => game.stop_search(false),
+            Some("ping") => send!("pong {}", args.first().unwrap_or(&"")),
+            Some("post") => engine::XBOARD_POST.store(true, Ordering::Relaxed),
+            Some("nopost") => engine::XBOARD_POST.store(false, Ordering::Relaxed),
+            Some("sd") => {
+                if let Some(depth) = args.first().and_then(|d| d.parse::<u8>().ok()) {
+                    game.max_depth = Some(depth.clamp(1, engine::MAX_PLY));
+                }
+            }
+            Some("setboard") => {
+                game.stop_search(true);
+                match ArrayBoard::parse_fen(&args.join(" ")) {
+                    Ok(board) => {
+                        game.board = board;
+                        game.history.clear();
+                    }
+                    Err(e) => send!("tellusererror Illegal position: {e}"),
+                }
+            }
+            Some("undo") | Some("remove") => {
+                game.stop_search(true);
+                let plies = if tokens[0] == "remove" { 2 } else { 1 };
+                for _ in 0..plies {
+                    if let Some(board) = game.history.pop() {
+                        game.board = board;
+                    }
+                }
+            }
+            Some("usermove") => {
+                let mv = args.first().unwrap_or(&"");
+                if !game.play(mv) {
+                    send!("Illegal move: {mv}");
+                } else {
+                    game.check_game_over();
+                    if game.engine_to_move() {
+                        game.start_search(&sender);
+                    }
+                }
+            }
+            Some("quit") => {
+                game.stop_search(true);
+                break;
+            }
+            Some(command) => send!("Error (unknown command): {command}"),
+        }
+        io::stdout().flush().ok();
+    }
+    io::stdout().flush().ok();
+}